@@ -18,6 +18,7 @@
 
 struct BuildingMaterial {
     glowing: u32,
+    tint: vec4<f32>,
 }
 
 @group(2) @binding(100)
@@ -31,6 +32,13 @@ fn fragment(
     // generate a PbrInput struct from the StandardMaterial bindings
     var pbr_input = pbr_input_from_standard_material(in, is_front);
 
+    // the color of the owning player
+    let base_color = pbr_input.material.base_color;
+    pbr_input.material.base_color = vec4<f32>(
+        mix(base_color.rgb, building_material.tint.rgb, building_material.tint.a),
+        base_color.a,
+    );
+
 //     // we can optionally modify the input before lighting and alpha_discard is applied
 //     pbr_input.material.base_color.b = pbr_input.material.base_color.r;
 
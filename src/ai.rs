@@ -0,0 +1,322 @@
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use crate::{
+    building::{construction::UnderConstruction, headquarters::HeadQuarters, tree::Tree, Building},
+    combat::Control,
+    command::{GameCommand, PlayerCommand},
+    economy::{ResourceKind, Stockpile},
+    game::{SimulationSet, SimulationSettings},
+    placement::Placement,
+    player::{Owner, Player},
+    replay::SimulationId,
+    unit_kind::UnitKind,
+    way::{Way, WayDirection, WayPath},
+};
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (Ai::attach, Ai::update)
+                .chain()
+                .in_set(SimulationSet::Interact)
+                .before(PlayerCommand::handle),
+        );
+    }
+}
+
+/// How well an [`Ai`] plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// Seconds between two decisions.
+    pub fn think_seconds(self) -> f32 {
+        match self {
+            Difficulty::Easy => 3.0,
+            Difficulty::Normal => 1.5,
+            Difficulty::Hard => 0.5,
+        }
+    }
+
+    /// Trees every head quarters harvests at once.
+    pub fn harvested_trees(self) -> usize {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Normal => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+
+    /// Wood to collect before each attack wave.
+    pub fn wood_per_wave(self) -> u32 {
+        match self {
+            Difficulty::Easy => 40,
+            Difficulty::Normal => 25,
+            Difficulty::Hard => 15,
+        }
+    }
+
+    /// How long head quarters produce soldiers instead of workers during an attack wave.
+    pub fn wave_seconds(self) -> f32 {
+        match self {
+            Difficulty::Easy => 15.0,
+            Difficulty::Normal => 25.0,
+            Difficulty::Hard => 40.0,
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!("unknown difficulty {name:?}, expected easy, normal or hard")),
+        }
+    }
+}
+
+/// Computer opponent controlling the [`Player`] it is attached to. It only acts through
+/// [`PlayerCommand`]s like the input, so it has to follow the same rules.
+///
+/// Head quarters harvest the closest trees until enough wood is stored for an attack wave, then
+/// produce soldiers and send them to the closest enemy or capturable neutral building for a
+/// while. Buildings producing nothing but soldiers attack all the time.
+#[derive(Component, Debug)]
+pub struct Ai {
+    pub difficulty: Difficulty,
+    think_timer: Timer,
+    /// Stored wood at which the next attack wave starts.
+    next_wave_wood: u32,
+    /// Seconds left of the current attack wave.
+    wave_left: f32,
+}
+
+/// What the [`Ai`] knows about a building when deciding.
+struct Observed {
+    entity: Entity,
+    id: SimulationId,
+    position: Vec3,
+    owner: Option<Owner>,
+    harvestable: bool,
+    capturable: bool,
+}
+
+impl Ai {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Ai {
+            difficulty,
+            think_timer: Timer::from_seconds(difficulty.think_seconds(), TimerMode::Repeating),
+            next_wave_wood: difficulty.wood_per_wave(),
+            wave_left: 0.0,
+        }
+    }
+
+    pub fn is_attacking(&self) -> bool {
+        self.wave_left > 0.0
+    }
+
+    /// Lets the AI take over the players [`SimulationSettings::ai`] assigns a difficulty to.
+    pub fn attach(
+        mut commands: Commands,
+        settings: Res<SimulationSettings>,
+        q_players: Query<(Entity, &Player), Added<Player>>,
+    ) {
+        for (entity, player) in q_players.iter() {
+            if let Some(difficulty) = settings.ai.get(&player.index) {
+                info!("{} is played by the {:?} AI", player.name, difficulty);
+                commands.entity(entity).insert(Ai::new(*difficulty));
+            }
+        }
+    }
+
+    /// Makes one decision per AI whenever it is time to think again, so harder AIs react faster.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn update(
+        time: Res<Time>,
+        mut q_ais: Query<(Entity, &mut Ai)>,
+        q_buildings: Query<(
+            Entity,
+            &SimulationId,
+            &Building,
+            &Transform,
+            Option<&Owner>,
+            Option<&Tree>,
+            Option<&Control>,
+            Has<UnderConstruction>,
+        )>,
+        q_head_quarters: Query<&HeadQuarters>,
+        q_stockpiles: Query<(&Stockpile, &Owner)>,
+        q_ways: Query<&Way>,
+        q_way_owners: Query<&Owner, With<Way>>,
+        q_players: Query<&Player>,
+        placement: Placement,
+        mut ev_player_command: EventWriter<PlayerCommand>,
+    ) {
+        // in a fixed order, so AIs break ties between equally good choices the same way
+        let mut buildings: Vec<_> = q_buildings
+            .iter()
+            .filter(|(.., under_construction)| !under_construction)
+            .map(|(entity, id, _, transform, owner, tree, control, _)| Observed {
+                entity,
+                id: *id,
+                position: transform.translation,
+                owner: owner.copied(),
+                harvestable: tree.is_some_and(|tree| tree.wood > 0),
+                capturable: tree.is_none() && control.is_some(),
+            })
+            .collect();
+        buildings.sort_by_key(|building| building.id);
+        let mut ais: Vec<_> = q_ais.iter_mut().collect();
+        ais.sort_by_key(|(player, _)| q_players.get(*player).map(|player| player.index).ok());
+
+        for (player, mut ai) in ais {
+            ai.think_timer.tick(time.delta());
+            ai.wave_left -= time.delta_seconds();
+            if !ai.think_timer.just_finished() {
+                continue;
+            }
+            let owner = Owner(player);
+            let index = q_players.get(player).unwrap().index;
+            let mut issue = |command| {
+                ev_player_command.send(PlayerCommand {
+                    player: index,
+                    command,
+                });
+            };
+            let wood: u32 = q_stockpiles
+                .iter()
+                .filter(|(_, stockpile_owner)| **stockpile_owner == owner)
+                .map(|(stockpile, _)| stockpile.amount(ResourceKind::Wood))
+                .sum();
+            if !ai.is_attacking() && wood >= ai.next_wave_wood {
+                info!("AI of {:?} attacks with {} wood stored", player, wood);
+                ai.wave_left = ai.difficulty.wave_seconds();
+                ai.next_wave_wood = wood + ai.difficulty.wood_per_wave();
+            }
+
+            let is_enemy = |other: &Observed| match other.owner {
+                Some(other) => owner.is_enemy_of(&other, &q_players),
+                None => other.capturable,
+            };
+            let is_target = |other: &Observed| !other.harvestable && is_enemy(other);
+            // straight ways that neither cross impassable terrain nor other buildings, so the
+            // command is accepted
+            let clear_way = |from: &Observed, to: &Observed| {
+                let path = WayPath::straight(from.position, to.position);
+                placement.is_way_clear(&path, &[from.entity, to.entity])
+            };
+
+            for producer in buildings.iter().filter(|building| building.owner == Some(owner)) {
+                let Ok(head_quarters) = q_head_quarters.get(producer.entity) else {
+                    continue;
+                };
+                let (_, _, building, ..) = q_buildings.get(producer.entity).unwrap();
+                // buildings storing the harvest only fight during attack waves
+                let harvests = q_stockpiles.contains(producer.entity);
+                let fights = !harvests || ai.is_attacking();
+
+                let unit_kind = if fights { UnitKind::Soldier } else { UnitKind::Worker };
+                if head_quarters.unit_kind != unit_kind {
+                    issue(GameCommand::SelectUnitKind {
+                        building: producer.entity,
+                        kind: unit_kind,
+                    });
+                    break;
+                }
+
+                let connected = |other: &Observed| building.way_to(other.entity, &q_ways).is_some();
+                let closest = |wanted: &dyn Fn(&Observed) -> bool| {
+                    buildings
+                        .iter()
+                        .filter(|other| wanted(other) && !connected(other))
+                        .filter(|other| clear_way(producer, other))
+                        .min_by(|a, b| {
+                            let distance =
+                                |other: &Observed| producer.position.distance(other.position);
+                            distance(a).total_cmp(&distance(b))
+                        })
+                };
+                let harvested =
+                    buildings.iter().filter(|other| other.harvestable && connected(other)).count();
+                let attacked = buildings.iter().any(|other| is_target(other) && connected(other));
+                let mut connect_to = None;
+                if harvests && harvested < ai.difficulty.harvested_trees() {
+                    connect_to = closest(&|other| other.harvestable);
+                }
+                if connect_to.is_none() && fights && !attacked {
+                    connect_to = closest(&is_target);
+                }
+                if let Some(target) = connect_to {
+                    issue(GameCommand::ConnectBuildings {
+                        from: producer.entity,
+                        to: target.entity,
+                        waypoints: Vec::new(),
+                    });
+                    break;
+                }
+
+                // soldiers are of no use at trees, neither are workers at enemies
+                let (harvest_weight, attack_weight) =
+                    if fights { (0, Way::MAX_WEIGHT) } else { (Way::DEFAULT_WEIGHT, 0) };
+                let mut adjusted = false;
+                for other in buildings.iter().filter(|other| connected(other)) {
+                    let way_entity = building.way_to(other.entity, &q_ways).unwrap();
+                    // like humans, AIs may only change their own ways
+                    if q_way_owners.get(way_entity) != Ok(&owner) {
+                        continue;
+                    }
+                    let way = q_ways.get(way_entity).unwrap();
+                    let weight = if other.harvestable {
+                        harvest_weight
+                    } else if is_target(other) {
+                        attack_weight
+                    } else {
+                        continue;
+                    };
+                    if way.weight != weight {
+                        issue(GameCommand::SetFlowWeight {
+                            way: way_entity,
+                            weight,
+                        });
+                        adjusted = true;
+                    }
+                    // enemies must not use attack ways to send their units back, and captured ways
+                    // may have been built from the target
+                    let away = if way.from == producer.entity {
+                        WayDirection::Forward
+                    } else {
+                        WayDirection::Backward
+                    };
+                    if is_target(other) && way.direction != away {
+                        // all toggles are applied in the same tick, so the way never leads back
+                        let mut direction = way.direction;
+                        while direction != away {
+                            issue(GameCommand::ToggleWayDirection {
+                                way: way_entity,
+                            });
+                            direction = direction.toggled();
+                        }
+                        adjusted = true;
+                    }
+                }
+                if adjusted {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,14 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
 use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::de::DeserializeOwned;
+
+use crate::unit_kind::{UnitKind, UnitKinds};
 
 pub struct AssetPlugin;
 
@@ -10,15 +20,88 @@ impl Plugin for AssetPlugin {
 
 #[derive(Resource)]
 pub struct AssetController {
-    pub head_quarters_scene: Handle<Scene>,
+    pub unit_scenes: BTreeMap<UnitKind, Handle<Scene>>,
 }
 
 impl AssetController {
-    pub fn setup(mut commands: Commands, asset_server: ResMut<AssetServer>) {
-        let scene = asset_server.load("models/towerRound_sampleF.glb#Scene0");
-
+    pub fn setup(
+        mut commands: Commands,
+        asset_server: ResMut<AssetServer>,
+        unit_kinds: Res<UnitKinds>,
+    ) {
         commands.insert_resource(AssetController {
-            head_quarters_scene: scene,
+            unit_scenes: UnitKind::ALL
+                .into_iter()
+                .map(|kind| (kind, asset_server.load(unit_kinds.get(kind).model.clone())))
+                .collect(),
         });
     }
 }
+
+/// Loads assets that are deserialized from RON files, like
+/// [`BuildingDefinition`](crate::building::definition::BuildingDefinition)s.
+pub struct RonLoader<A> {
+    extensions: &'static [&'static str],
+    _asset: PhantomData<fn() -> A>,
+}
+
+impl<A> RonLoader<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        RonLoader {
+            extensions,
+            _asset: PhantomData,
+        }
+    }
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = RonLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<A, RonLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+#[derive(Debug)]
+pub enum RonLoaderError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for RonLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RonLoaderError::Io(error) => write!(f, "could not read file: {error}"),
+            RonLoaderError::Ron(error) => write!(f, "invalid RON: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RonLoaderError {}
+
+impl From<std::io::Error> for RonLoaderError {
+    fn from(error: std::io::Error) -> Self {
+        RonLoaderError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for RonLoaderError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        RonLoaderError::Ron(error)
+    }
+}
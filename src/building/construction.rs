@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+
+use super::{
+    definition::{BuildingDefinition, BuildingDefinitions},
+    Building,
+};
+use crate::{
+    economy::Stockpile,
+    game::SimulationSet,
+    input::{InputController, InputEvent},
+    placement::Placement,
+    player::{LocalPlayer, Owner, Player, Players},
+    replay::SimulationId,
+    terrain::Terrain,
+    unit::{Unit, UnitArrived},
+};
+
+pub struct ConstructionPlugin;
+
+impl Plugin for ConstructionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaceBuilding>().add_systems(
+            FixedUpdate,
+            (
+                PlaceBuilding::handle.in_set(SimulationSet::Interact),
+                UnderConstruction::unit_arrived.in_set(SimulationSet::Update).after(Unit::update),
+            ),
+        );
+    }
+}
+
+/// Starts constructing a building where nothing else stands, see
+/// [`Placement::is_building_clear`], if the stockpiles can pay for it.
+#[derive(Event, Debug)]
+pub struct PlaceBuilding {
+    /// Name of the definition in [`BuildingDefinitions`], which needs a
+    /// [`Construction`](super::definition::Construction).
+    pub definition: String,
+    pub position: Vec3,
+    /// The [`Player`](crate::player::Player) entity paying for the building, `None` to pay
+    /// from neutral stockpiles.
+    pub owner: Option<Entity>,
+}
+
+impl PlaceBuilding {
+    pub fn handle(
+        mut commands: Commands,
+        mut ev_place_building: EventReader<PlaceBuilding>,
+        definitions: Res<BuildingDefinitions>,
+        definition_assets: Res<Assets<BuildingDefinition>>,
+        placement: Placement,
+        mut q_stockpiles: Query<
+            (&SimulationId, &mut Stockpile, Option<&Owner>),
+            Without<UnderConstruction>,
+        >,
+    ) {
+        // buildings placed by this run are not visible to `placement` yet
+        let mut placed: Vec<Rect> = Vec::new();
+
+        for event in ev_place_building.read() {
+            info!(target: "events", "{:?}", event);
+            let Some((handle, definition)) = definitions
+                .get(&event.definition)
+                .and_then(|handle| Some((handle, definition_assets.get(handle)?)))
+            else {
+                continue;
+            };
+            let Some(construction) = &definition.construction else {
+                continue;
+            };
+            let footprint = definition.footprint(event.position);
+            if !placement.is_building_clear(definition, event.position)
+                || placed.iter().any(|other| !other.intersect(footprint).is_empty())
+            {
+                continue;
+            }
+            // stockpiles pay in a fixed order, so the simulation stays deterministic
+            let mut stockpiles: Vec<_> = q_stockpiles
+                .iter_mut()
+                .filter(|(_, _, owner)| owner.map(|owner| owner.0) == event.owner)
+                .map(|(id, stockpile, _)| (*id, stockpile))
+                .collect();
+            stockpiles.sort_by_key(|(id, _)| *id);
+            if !construction.is_affordable(stockpiles.iter().map(|(_, stockpile)| &**stockpile)) {
+                continue;
+            }
+            for (kind, cost) in &construction.cost {
+                let mut left = *cost;
+                for (_, stockpile) in stockpiles.iter_mut() {
+                    left -= stockpile.take(*kind, left);
+                }
+            }
+
+            let building =
+                definition.spawn(&mut commands, handle.clone(), event.position, event.owner);
+            if construction.work > 0 {
+                commands.entity(building).insert(UnderConstruction {
+                    remaining: construction.work,
+                    work: construction.work,
+                });
+            }
+            placed.push(footprint);
+        }
+    }
+}
+
+/// A placed building that does nothing until enough units arrived to finish it.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct UnderConstruction {
+    /// Units still needed.
+    pub remaining: u32,
+    pub work: u32,
+}
+
+impl UnderConstruction {
+    pub fn progress(&self) -> f32 {
+        1.0 - self.remaining as f32 / self.work as f32
+    }
+
+    /// Counts the units of the owner and its allies, enemies attack the site instead, see
+    /// [`Garrison::unit_arrived`](crate::combat::Garrison::unit_arrived).
+    pub fn unit_arrived(
+        mut commands: Commands,
+        mut ev_unit_arrived: EventReader<UnitArrived>,
+        mut q_sites: Query<(&mut UnderConstruction, Option<&Owner>)>,
+        q_players: Query<&Player>,
+    ) {
+        for event in ev_unit_arrived.read() {
+            let Ok((mut site, owner)) = q_sites.get_mut(event.building) else {
+                continue;
+            };
+            if let (Some(unit_owner), Some(owner)) = (event.owner, owner) {
+                if unit_owner.is_enemy_of(owner, &q_players) {
+                    continue;
+                }
+            }
+            // several units may arrive in the same tick
+            if site.remaining == 0 {
+                continue;
+            }
+            site.remaining -= 1;
+            if site.remaining == 0 {
+                info!("finished construction of {:?}", event.building);
+                commands.entity(event.building).remove::<UnderConstruction>();
+            }
+        }
+    }
+}
+
+/// The building the player is about to place, previewed by a ghost following the cursor.
+#[derive(Resource)]
+pub struct BuildMode {
+    /// Name of the selected definition, `None` outside of build mode.
+    pub selected: Option<String>,
+    /// Whether the building could be placed where the ghost is.
+    pub placing_valid: bool,
+    ghost: Entity,
+    material: Handle<StandardMaterial>,
+}
+
+impl BuildMode {
+    pub fn setup(
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+    ) {
+        let material = materials.add(StandardMaterial {
+            base_color: Color::rgba(0.3, 0.5, 0.3, 0.5),
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        });
+        let ghost = commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Cuboid::default()),
+                material: material.clone(),
+                visibility: Visibility::Hidden,
+                ..default()
+            })
+            .id();
+        commands.insert_resource(BuildMode {
+            selected: None,
+            placing_valid: false,
+            ghost,
+            material,
+        });
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.selected.is_some()
+    }
+
+    /// Selects the building to place, clicking places it through
+    /// [`input::issue_commands`](crate::input::issue_commands).
+    pub fn handle_input(
+        mut ev_input: EventReader<InputEvent>,
+        mut build_mode: ResMut<BuildMode>,
+        definitions: Res<BuildingDefinitions>,
+        definition_assets: Res<Assets<BuildingDefinition>>,
+    ) {
+        for event in ev_input.read() {
+            match *event {
+                InputEvent::CycleBuildMode => {
+                    // definitions players can construct, then back to no building at all
+                    let constructible: Vec<_> = definitions
+                        .names()
+                        .filter(|name| {
+                            definitions
+                                .get(name)
+                                .and_then(|handle| definition_assets.get(handle))
+                                .is_some_and(|definition| definition.construction.is_some())
+                        })
+                        .collect();
+                    let next = match &build_mode.selected {
+                        Some(selected) => constructible
+                            .iter()
+                            .position(|name| name == selected)
+                            .and_then(|index| constructible.get(index + 1)),
+                        None => constructible.first(),
+                    };
+                    build_mode.selected = next.map(|name| name.to_string());
+                }
+                InputEvent::Abort => {
+                    build_mode.selected = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Moves the ghost to the cell under the cursor and colors it by whether the selected
+    /// building could be placed there.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_ghost(
+        mut build_mode: ResMut<BuildMode>,
+        mut q_ghost: Query<(&mut Transform, &mut Visibility), Without<Building>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        input_controller: Res<InputController>,
+        definitions: Res<BuildingDefinitions>,
+        definition_assets: Res<Assets<BuildingDefinition>>,
+        placement: Placement,
+        q_stockpiles: Query<(&Stockpile, Option<&Owner>), Without<UnderConstruction>>,
+        local_player: Res<LocalPlayer>,
+        players: Res<Players>,
+    ) {
+        let (mut transform, mut visibility) = q_ghost.get_mut(build_mode.ghost).unwrap();
+        let selected = build_mode
+            .selected
+            .as_ref()
+            .and_then(|name| definition_assets.get(definitions.get(name)?));
+        let (Some(definition), Some(plane_position)) = (selected, input_controller.plane_position)
+        else {
+            *visibility = Visibility::Hidden;
+            build_mode.placing_valid = false;
+            return;
+        };
+
+        let position = Terrain::cell_center(Terrain::cell(plane_position));
+        let owner = local_player.entity(&players);
+        let stockpiles = q_stockpiles
+            .iter()
+            .filter(|(_, stockpile_owner)| stockpile_owner.map(|owner| owner.0) == owner)
+            .map(|(stockpile, _)| stockpile);
+        let (x, y, z) = definition.collider_size;
+        let size = Vec3::new(x, y, z) * definition.scale;
+        *visibility = Visibility::Visible;
+        *transform =
+            Transform::from_translation(position + Vec3::Y * size.y / 2.0).with_scale(size);
+
+        // the same checks as `PlaceBuilding::handle`
+        build_mode.placing_valid = placement.is_building_clear(definition, position)
+            && definition
+                .construction
+                .as_ref()
+                .is_some_and(|construction| construction.is_affordable(stockpiles));
+
+        let material = materials.get_mut(build_mode.material.id()).unwrap();
+        if build_mode.placing_valid {
+            material.base_color = Color::rgba(0.3, 0.5, 0.3, 0.5);
+        } else {
+            material.base_color = Color::rgba(0.8, 0.3, 0.3, 0.5);
+        }
+    }
+}
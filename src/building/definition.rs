@@ -0,0 +1,442 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::asset::{LoadedFolder, RecursiveDependencyLoadState};
+use bevy::prelude::*;
+use bevy_xpbd_3d::plugins::collision::Collider;
+use serde::Deserialize;
+
+use super::headquarters::HeadQuarters;
+use super::tree::Tree;
+use super::Building;
+use crate::combat::{Control, Garrison, Health};
+use crate::economy::{ResourceKind, Stockpile};
+use crate::player::Owner;
+use crate::unit_kind::UnitKind;
+
+/// Describes a kind of building, loaded from a `*.building.ron` file in
+/// [`BuildingDefinitions::FOLDER`].
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct BuildingDefinition {
+    /// Display name.
+    pub name: String,
+    /// Scene asset path.
+    pub model: String,
+    #[serde(default = "BuildingDefinition::default_scale")]
+    pub scale: f32,
+    /// Size of the cuboid collider before scaling.
+    pub collider_size: (f32, f32, f32),
+    /// Units the building sends along its ways, like the head quarters.
+    #[serde(default)]
+    pub production: Option<Production>,
+    /// Whether delivered resources are stored in a [`Stockpile`](crate::economy::Stockpile).
+    #[serde(default)]
+    pub storage: bool,
+    #[serde(default)]
+    pub behaviours: Vec<Behaviour>,
+    /// Damage enemy units have to deal to destroy it, buildings without can't be attacked.
+    #[serde(default)]
+    pub health: Option<u32>,
+    /// Units of other players that have to arrive to capture it, buildings without can't be
+    /// captured. Capturable buildings are never destroyed.
+    #[serde(default)]
+    pub control: Option<u32>,
+    /// Friendly fighters that can stay in the building to defend it.
+    #[serde(default)]
+    pub garrison: u32,
+    /// How players build it, buildings without can only be part of a map.
+    #[serde(default)]
+    pub construction: Option<Construction>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Production {
+    /// Kind produced until the player selects another one.
+    #[serde(default)]
+    pub unit_kind: UnitKind,
+    /// Seconds between two units.
+    pub cooldown: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Construction {
+    /// Resources taken from the stockpiles when the construction starts.
+    pub cost: BTreeMap<ResourceKind, u32>,
+    /// Units that have to arrive at the construction site to finish it.
+    pub work: u32,
+}
+
+impl Construction {
+    pub fn is_affordable<'a>(&self, stockpiles: impl IntoIterator<Item = &'a Stockpile>) -> bool {
+        let mut available = BTreeMap::new();
+        for stockpile in stockpiles {
+            for (kind, amount) in stockpile.iter() {
+                *available.entry(kind).or_insert(0) += amount;
+            }
+        }
+        self.cost.iter().all(|(kind, cost)| available.get(kind).copied().unwrap_or(0) >= *cost)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Behaviour {
+    /// Units harvest wood from the building until it runs out, see [`Tree`](super::tree::Tree).
+    Harvestable {
+        wood: u32,
+        /// Scene shown once half of the wood is harvested.
+        #[serde(default)]
+        depleted_model: Option<String>,
+    },
+}
+
+impl BuildingDefinition {
+    fn default_scale() -> f32 {
+        1.0
+    }
+
+    pub fn collider(&self) -> Collider {
+        let (x, y, z) = self.collider_size;
+        Collider::cuboid(x, y, z)
+    }
+
+    /// Ground area covered by the collider when placed at `position`.
+    pub fn footprint(&self, position: Vec3) -> Rect {
+        let (x, _, z) = self.collider_size;
+        Rect::from_center_size(position.xz(), Vec2::new(x, z) * self.scale)
+    }
+
+    /// Spawns a building with the components this definition describes, neutral without an
+    /// owner.
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        handle: Handle<BuildingDefinition>,
+        position: Vec3,
+        owner: Option<Entity>,
+    ) -> Entity {
+        let mut building = commands.spawn((
+            Building::default(),
+            handle,
+            TransformBundle::from_transform(
+                Transform::from_translation(position).with_scale(Vec3::splat(self.scale)),
+            ),
+            self.collider(),
+        ));
+        if let Some(owner) = owner {
+            building.insert(Owner(owner));
+        }
+        if let Some(production) = &self.production {
+            building.insert(HeadQuarters::new(production));
+        }
+        if self.storage {
+            building.insert(Stockpile::default());
+        }
+        if let Some(health) = self.health {
+            building.insert(Health {
+                current: health,
+                max: health,
+            });
+        }
+        if let Some(control) = self.control {
+            building.insert(Control {
+                points: control,
+                max: control,
+            });
+        }
+        if self.garrison > 0 {
+            building.insert(Garrison {
+                units: 0,
+                capacity: self.garrison,
+            });
+        }
+        for behaviour in &self.behaviours {
+            match *behaviour {
+                Behaviour::Harvestable {
+                    wood,
+                    ..
+                } => {
+                    building.insert(Tree {
+                        wood,
+                        initial_wood: wood,
+                    });
+                }
+            }
+        }
+        building.id()
+    }
+
+    pub fn depleted_model(&self) -> Option<&str> {
+        self.behaviours.iter().find_map(|behaviour| match behaviour {
+            Behaviour::Harvestable {
+                depleted_model,
+                ..
+            } => depleted_model.as_deref(),
+        })
+    }
+
+    /// Applies a reloaded definition to the buildings spawned from it, adding and removing
+    /// components like [`BuildingDefinition::spawn`] would. The state of components that stay,
+    /// like stored resources or damage taken, is kept within the new limits.
+    #[allow(clippy::type_complexity)]
+    pub fn apply_changes(
+        mut commands: Commands,
+        mut ev_asset: EventReader<AssetEvent<BuildingDefinition>>,
+        definitions: Res<Assets<BuildingDefinition>>,
+        mut q_buildings: Query<(
+            Entity,
+            &mut Handle<BuildingDefinition>,
+            &mut Transform,
+            &mut Collider,
+            Option<&mut HeadQuarters>,
+            Has<Stockpile>,
+            Option<&mut Health>,
+            Option<&mut Control>,
+            Option<&mut Garrison>,
+            Option<&mut Tree>,
+        )>,
+    ) {
+        for event in ev_asset.read() {
+            let AssetEvent::Modified {
+                id,
+            } = *event
+            else {
+                continue;
+            };
+            let Some(definition) = definitions.get(id) else {
+                continue;
+            };
+            info!("reloaded building definition {:?}", definition.name);
+            let wood = definition
+                .behaviours
+                .iter()
+                .map(|behaviour| match *behaviour {
+                    Behaviour::Harvestable {
+                        wood,
+                        ..
+                    } => wood,
+                })
+                .next();
+            for (
+                entity,
+                mut handle,
+                mut transform,
+                mut collider,
+                head_quarters,
+                has_stockpile,
+                health,
+                control,
+                garrison,
+                tree,
+            ) in q_buildings.iter_mut()
+            {
+                if handle.id() != id {
+                    continue;
+                }
+                transform.scale = Vec3::splat(definition.scale);
+                *collider = definition.collider();
+                let mut building = commands.entity(entity);
+                match (head_quarters, &definition.production) {
+                    (Some(mut head_quarters), Some(production)) => {
+                        head_quarters
+                            .spawn_timer
+                            .set_duration(Duration::from_secs_f32(production.cooldown));
+                    }
+                    (None, Some(production)) => {
+                        building.insert(HeadQuarters::new(production));
+                    }
+                    (_, None) => {
+                        building.remove::<HeadQuarters>();
+                    }
+                }
+                match (has_stockpile, definition.storage) {
+                    (false, true) => {
+                        building.insert(Stockpile::default());
+                    }
+                    (true, false) => {
+                        building.remove::<Stockpile>();
+                    }
+                    _ => {}
+                }
+                match (health, definition.health) {
+                    (Some(mut health), Some(max)) => {
+                        health.max = max;
+                        health.current = health.current.min(max);
+                    }
+                    (None, Some(max)) => {
+                        building.insert(Health {
+                            current: max,
+                            max,
+                        });
+                    }
+                    (_, None) => {
+                        building.remove::<Health>();
+                    }
+                }
+                match (control, definition.control) {
+                    (Some(mut control), Some(max)) => {
+                        control.max = max;
+                        control.points = control.points.min(max);
+                    }
+                    (None, Some(max)) => {
+                        building.insert(Control {
+                            points: max,
+                            max,
+                        });
+                    }
+                    (_, None) => {
+                        building.remove::<Control>();
+                    }
+                }
+                match (garrison, definition.garrison) {
+                    (_, 0) => {
+                        building.remove::<Garrison>();
+                    }
+                    (Some(mut garrison), capacity) => {
+                        garrison.capacity = capacity;
+                        garrison.units = garrison.units.min(capacity);
+                    }
+                    (None, capacity) => {
+                        building.insert(Garrison {
+                            units: 0,
+                            capacity,
+                        });
+                    }
+                }
+                match (tree, wood) {
+                    (Some(mut tree), Some(wood)) => {
+                        tree.initial_wood = wood;
+                        tree.wood = tree.wood.min(wood);
+                    }
+                    (None, Some(wood)) => {
+                        building.insert(Tree {
+                            wood,
+                            initial_wood: wood,
+                        });
+                    }
+                    (_, None) => {
+                        building.remove::<Tree>();
+                    }
+                }
+                // lets the visuals pick up a changed model
+                handle.set_changed();
+            }
+        }
+    }
+}
+
+/// All building definitions, by their file name without the extension.
+#[derive(Resource)]
+pub struct BuildingDefinitions {
+    folder: Handle<LoadedFolder>,
+    definitions: BTreeMap<String, Handle<BuildingDefinition>>,
+    /// Whether the folder finished loading, even if no definition could be loaded from it.
+    loaded: bool,
+}
+
+impl BuildingDefinitions {
+    pub const FOLDER: &'static str = "buildings";
+    pub const EXTENSION: &'static str = "building.ron";
+    /// The definition [`WinCondition::DestroyHeadQuarters`](crate::map::WinCondition) is about.
+    pub const HEAD_QUARTERS: &'static str = "head_quarters";
+
+    pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+        commands.insert_resource(BuildingDefinitions {
+            folder: asset_server.load_folder(Self::FOLDER),
+            definitions: BTreeMap::new(),
+            loaded: false,
+        });
+    }
+
+    /// Registers the definitions once all files in the folder finished loading, and new ones
+    /// whenever the file watcher loads the folder again because files were added to it. Files
+    /// that failed to load are left out, without any definition the app exits, as there is
+    /// nothing to play.
+    pub fn update(
+        mut definitions: ResMut<BuildingDefinitions>,
+        asset_server: Res<AssetServer>,
+        folders: Res<Assets<LoadedFolder>>,
+        definition_assets: Res<Assets<BuildingDefinition>>,
+        mut ev_folder: EventReader<AssetEvent<LoadedFolder>>,
+        mut ev_app_exit: EventWriter<AppExit>,
+    ) {
+        let folder = definitions.folder.id();
+        let reloaded = ev_folder
+            .read()
+            .filter(|event| event.is_modified(folder) || event.is_loaded_with_dependencies(folder))
+            .count()
+            > 0;
+        if definitions.loaded {
+            if reloaded {
+                let added: Vec<_> = definitions
+                    .scan(&folders, &definition_assets)
+                    .into_iter()
+                    .filter(|(name, _)| !definitions.definitions.contains_key(name))
+                    .collect();
+                if !added.is_empty() {
+                    info!(
+                        "loaded new building definitions {:?}",
+                        added.iter().map(|(name, _)| name)
+                    );
+                    definitions.definitions.extend(added);
+                }
+            }
+            return;
+        }
+        if !matches!(
+            asset_server.get_recursive_dependency_load_state(&definitions.folder),
+            Some(RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed)
+        ) {
+            return;
+        }
+        definitions.loaded = true;
+        definitions.definitions = definitions.scan(&folders, &definition_assets);
+        if definitions.definitions.is_empty() {
+            error!("found no building definitions in {:?}", Self::FOLDER);
+            ev_app_exit.send(AppExit);
+            return;
+        }
+        info!("loaded building definitions {:?}", definitions.definitions.keys());
+    }
+
+    /// The definitions of all files in the folder that loaded, by name.
+    fn scan(
+        &self,
+        folders: &Assets<LoadedFolder>,
+        definition_assets: &Assets<BuildingDefinition>,
+    ) -> BTreeMap<String, Handle<BuildingDefinition>> {
+        let suffix = format!(".{}", Self::EXTENSION);
+        folders
+            .get(&self.folder)
+            .into_iter()
+            .flat_map(|folder| &folder.handles)
+            .filter_map(|handle| {
+                let file_name = handle.path()?.path().file_name()?.to_str()?;
+                let name = file_name.strip_suffix(&suffix)?.to_owned();
+                let handle = handle.clone().try_typed::<BuildingDefinition>().ok()?;
+                if !definition_assets.contains(&handle) {
+                    error!("could not load building definition {:?}", handle.path());
+                    return None;
+                }
+                Some((name, handle))
+            })
+            .collect()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded && !self.definitions.is_empty()
+    }
+
+    /// Run condition for systems that need the definitions.
+    pub fn loaded(definitions: Option<Res<BuildingDefinitions>>) -> bool {
+        definitions.is_some_and(|definitions| definitions.is_loaded())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Handle<BuildingDefinition>> {
+        self.definitions.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.definitions.keys().map(String::as_str)
+    }
+}
@@ -1,66 +1,46 @@
 use bevy::prelude::*;
-use bevy_xpbd_3d::plugins::collision::Collider;
 
-use crate::unit::{SpawnUnit, Unit};
+use crate::{
+    game::SimulationSet,
+    replay::SimulationId,
+    unit::{Lanes, SpawnUnit, Unit, WaitingUnits},
+    unit_kind::UnitKind,
+    way::{FlowDistributor, Way},
+};
 
-use super::Building;
+use super::{construction::UnderConstruction, definition::Production, Building};
 
 pub struct HeadQuartersPlugin;
 
 impl Plugin for HeadQuartersPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<HeadQuartersSpawner>()
-            .add_systems(Startup, HeadQuartersSpawner::setup)
-            .add_event::<SpawnHeadQuarters>()
-            .add_systems(Update, (SpawnHeadQuarters::handle, HeadQuarters::update));
+        app.add_event::<SelectUnitKind>().add_systems(
+            FixedUpdate,
+            (
+                SelectUnitKind::handle.in_set(SimulationSet::Interact),
+                HeadQuarters::update.in_set(SimulationSet::Update),
+            ),
+        );
     }
 }
 
-#[derive(Resource, Reflect)]
-pub struct HeadQuartersSpawner {
-    pub scene: Handle<Scene>,
-    pub default_cooldown: f32,
+/// Changes which kind of units a head quarters produces.
+#[derive(Event, Debug)]
+pub struct SelectUnitKind {
+    pub building: Entity,
+    pub kind: UnitKind,
 }
 
-impl HeadQuartersSpawner {
-    pub fn setup(mut commands: Commands, asset_server: ResMut<AssetServer>) {
-        let scene = asset_server.load("models/towerRound_sampleF.glb#Scene0");
-
-        commands.insert_resource(HeadQuartersSpawner {
-            scene,
-            default_cooldown: 5.0,
-        });
-    }
-}
-
-#[derive(Event)]
-pub struct SpawnHeadQuarters {
-    pub position: Vec3,
-}
-
-impl SpawnHeadQuarters {
+impl SelectUnitKind {
     pub fn handle(
-        mut commands: Commands,
-        head_quaters_spawner: ResMut<HeadQuartersSpawner>,
-        mut spawn_head_quarters: EventReader<SpawnHeadQuarters>,
+        mut ev_select_unit_kind: EventReader<SelectUnitKind>,
+        mut q_head_quarters: Query<&mut HeadQuarters>,
     ) {
-        for event in spawn_head_quarters.read() {
-            commands.spawn((
-                HeadQuarters {
-                    spawn_timer: Timer::from_seconds(
-                        head_quaters_spawner.default_cooldown,
-                        TimerMode::Repeating,
-                    ),
-                    cursor: 0,
-                },
-                Building::default(),
-                SceneBundle {
-                    scene: head_quaters_spawner.scene.clone(),
-                    transform: Transform::from_translation(event.position),
-                    ..default()
-                },
-                Collider::cuboid(1.0, 1.0, 1.0),
-            ));
+        for event in ev_select_unit_kind.read() {
+            info!(target: "events", "{:?}", event);
+            if let Ok(mut head_quarters) = q_head_quarters.get_mut(event.building) {
+                head_quarters.unit_kind = event.kind;
+            }
         }
     }
 }
@@ -68,36 +48,74 @@ impl SpawnHeadQuarters {
 #[derive(Component)]
 pub struct HeadQuarters {
     pub spawn_timer: Timer,
-    cursor: usize,
+    flow: FlowDistributor,
+    pub unit_kind: UnitKind,
 }
 
 impl HeadQuarters {
+    pub fn new(production: &Production) -> Self {
+        HeadQuarters {
+            spawn_timer: Timer::from_seconds(production.cooldown, TimerMode::Repeating),
+            flow: FlowDistributor::default(),
+            unit_kind: production.unit_kind,
+        }
+    }
+
     pub fn update(
         time: Res<Time>,
-        mut head_quarters: Query<(Entity, &mut HeadQuarters, &Building, &Transform)>,
+        mut head_quarters: Query<
+            (Entity, &mut HeadQuarters, &Building),
+            Without<UnderConstruction>,
+        >,
+        q_ways: Query<&Way>,
+        q_units: Query<(Entity, &Unit, &SimulationId)>,
+        waiting: Res<WaitingUnits>,
         mut ev_spawn_unit: EventWriter<SpawnUnit>,
     ) {
-        for (entity, mut head_quarters, building, transform) in head_quarters.iter_mut() {
+        let lanes = Lanes::new(q_units.iter());
+        for (entity, mut head_quarters, building) in head_quarters.iter_mut() {
+            let outflows = building.outflows(entity, &q_ways);
+            // back-pressure: the timer pauses while no way units could be sent along has room, or
+            // units it sent are still waiting to leave
+            let open_outflows: Vec<_> = outflows
+                .iter()
+                .copied()
+                .filter(|(way_entity, weight)| {
+                    *weight > 0
+                        && waiting.at(entity) == 0
+                        && q_ways.get(*way_entity).is_ok_and(|way| {
+                            lanes.has_room((*way_entity, way.to == entity), way.capacity())
+                        })
+                })
+                .collect();
+            if open_outflows.is_empty() {
+                // without any way to send units along, the next unit gets ready and leaves as
+                // soon as a way allows it
+                if outflows.iter().all(|(_, weight)| *weight == 0) {
+                    let timer = &mut head_quarters.spawn_timer;
+                    timer.set_elapsed((timer.elapsed() + time.delta()).min(timer.duration()));
+                }
+                continue;
+            }
             head_quarters.spawn_timer.tick(time.delta());
-            if building.connected.is_empty() {
-                head_quarters.spawn_timer.set_mode(TimerMode::Once);
-            } else {
-                if head_quarters.spawn_timer.finished() {
-                    dbg!("spawn");
-                    for _ in 0..head_quarters.spawn_timer.times_finished_this_tick().max(1) {
-                        head_quarters.cursor %= building.connected.len();
-                        let unit = Unit {
-                            from_building: entity,
-                            to_building: building.connected[head_quarters.cursor],
-                        };
-                        head_quarters.cursor += 1;
-                        ev_spawn_unit.send(SpawnUnit {
-                            unit,
-                        });
-                        head_quarters.spawn_timer.reset();
-                    }
+            if head_quarters.spawn_timer.just_finished() {
+                dbg!("spawn");
+                for _ in 0..head_quarters.spawn_timer.times_finished_this_tick() {
+                    let Some(destination) = head_quarters
+                        .flow
+                        .next(&outflows, |way| open_outflows.iter().any(|(open, _)| *open == way))
+                        .and_then(|way| q_ways.get(way).ok()?.destination_from(entity))
+                    else {
+                        break;
+                    };
+                    ev_spawn_unit.send(SpawnUnit {
+                        kind: head_quarters.unit_kind,
+                        from_building: entity,
+                        destination,
+                        home: entity,
+                        cargo: None,
+                    });
                 }
-                head_quarters.spawn_timer.set_mode(TimerMode::Repeating);
             }
         }
     }
@@ -4,12 +4,21 @@ use bevy::prelude::*;
 use bevy::render::render_resource::{AsBindGroup, ShaderRef};
 use bevy::scene::SceneInstanceReady;
 
+use crate::assets::RonLoader;
+use crate::game::SimulationSet;
 use crate::input::{InputController, InputEvent};
-use crate::way::InteractWay;
+use crate::player::{Owner, Player};
+use crate::terrain::Terrain;
+use crate::unit::{SpawnUnit, Unit};
+use crate::way::{InteractWay, Way};
 
+use self::construction::{BuildMode, ConstructionPlugin};
+use self::definition::{BuildingDefinition, BuildingDefinitions};
 use self::headquarters::HeadQuartersPlugin;
-use self::tree::TreePlugin;
+use self::tree::{Tree, TreePlugin};
 
+pub mod construction;
+pub mod definition;
 pub mod headquarters;
 pub mod tree;
 
@@ -21,6 +30,7 @@ impl PluginGroup for BuildingPlugins {
             .add(BuildingPlugin)
             .add(HeadQuartersPlugin)
             .add(TreePlugin)
+            .add(ConstructionPlugin)
     }
 }
 
@@ -28,14 +38,41 @@ pub struct BuildingPlugin;
 
 impl Plugin for BuildingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, BuildingAssets::setup)
+        app.init_asset::<BuildingDefinition>()
+            .register_asset_loader(RonLoader::<BuildingDefinition>::new(&[
+                BuildingDefinitions::EXTENSION,
+            ]))
+            .add_event::<SpawnBuilding>()
+            .add_event::<DespawnBuilding>()
+            .add_systems(Startup, BuildingDefinitions::setup)
+            .add_systems(PreUpdate, BuildingDefinitions::update)
+            .add_systems(Update, BuildingDefinition::apply_changes)
+            .add_systems(
+                FixedUpdate,
+                (
+                    SpawnBuilding::handle.in_set(SimulationSet::Spawn),
+                    // after spawning the units a building sent off right before it was despawned
+                    DespawnBuilding::handle.in_set(SimulationSet::Spawn).after(SpawnUnit::handle),
+                ),
+            );
+    }
+}
+
+pub struct BuildingVisualsPlugin;
+
+impl Plugin for BuildingVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (BuildingAssets::setup, BuildMode::setup))
             .add_plugins(MaterialPlugin::<BuildingExtendedMaterial>::default())
             .add_systems(
                 Update,
                 (
+                    Building::insert_scene,
+                    (BuildMode::handle_input, BuildMode::update_ghost).chain(),
                     BuildingAssets::on_building_scene_loaded,
                     BuildingAssets::on_instancing_scene,
                     Building::update_glowing,
+                    Building::update_tint,
                 ),
             );
     }
@@ -55,6 +92,7 @@ impl BuildingAssets {
             },
             extension: BuildingMaterialExtension {
                 glowing: 0,
+                tint: Vec4::ZERO,
             },
         });
         commands.insert_resource(BuildingAssets {
@@ -86,6 +124,7 @@ impl BuildingAssets {
                             base: material.clone(),
                             extension: BuildingMaterialExtension {
                                 glowing: 0,
+                                tint: Vec4::ZERO,
                             },
                         });
                         (entity, extended_material)
@@ -108,8 +147,11 @@ impl BuildingAssets {
         q_children: Query<(Entity, &Children)>,
         mut q_materials: Query<&mut Handle<BuildingExtendedMaterial>>, //or use Added filter?
         mut materials: ResMut<Assets<BuildingExtendedMaterial>>,
+        q_owners: Query<&Owner>,
+        q_players: Query<&Player>,
     ) {
         for event in ev_scene_ready.read() {
+            let tint = Building::tint(q_owners.get(event.parent).ok(), &q_players);
             dbg!(event);
             let (_, children) = q_children.get(event.parent).unwrap();
             //let scene_instance = q_scene_instances.get(*child).unwrap();
@@ -121,7 +163,8 @@ impl BuildingAssets {
             let mut material_handles = q_materials.iter_many_mut(children);
 
             while let Some(mut material_handle) = material_handles.fetch_next() {
-                let material = materials.get_mut(material_handle.id()).unwrap().clone();
+                let mut material = materials.get_mut(material_handle.id()).unwrap().clone();
+                material.extension.tint = tint;
                 let new_material = materials.add(material);
                 *material_handle = new_material;
                 dbg!(material_handle.id());
@@ -136,6 +179,9 @@ type BuildingExtendedMaterial = ExtendedMaterial<StandardMaterial, BuildingMater
 pub struct BuildingMaterialExtension {
     #[uniform(100)]
     glowing: u32,
+    /// Color mixed into the base color by its alpha.
+    #[uniform(100)]
+    tint: Vec4,
 }
 
 impl MaterialExtension for BuildingMaterialExtension {
@@ -148,13 +194,90 @@ impl MaterialExtension for BuildingMaterialExtension {
     }
 }
 
+/// Spawns a building as described by its [`BuildingDefinition`].
+#[derive(Event, Debug)]
+pub struct SpawnBuilding {
+    /// Name of the definition in [`BuildingDefinitions`].
+    pub definition: String,
+    pub position: Vec3,
+    /// The [`Player`](crate::player::Player) entity, `None` for neutral buildings.
+    pub owner: Option<Entity>,
+}
+
+impl SpawnBuilding {
+    pub fn handle(
+        mut commands: Commands,
+        mut ev_spawn_building: EventReader<SpawnBuilding>,
+        definitions: Res<BuildingDefinitions>,
+        definition_assets: Res<Assets<BuildingDefinition>>,
+        terrain: Res<Terrain>,
+    ) {
+        for event in ev_spawn_building.read() {
+            info!(target: "events", "{:?}", event);
+            if !terrain.is_buildable(event.position) {
+                warn!("can't build on the terrain at {}", event.position);
+                continue;
+            }
+            let Some((handle, definition)) = definitions
+                .get(&event.definition)
+                .and_then(|handle| Some((handle, definition_assets.get(handle)?)))
+            else {
+                warn!("unknown building definition {:?}", event.definition);
+                continue;
+            };
+            definition.spawn(&mut commands, handle.clone(), event.position, event.owner);
+        }
+    }
+}
+
+/// Removes a building and its ways.
+#[derive(Event, Debug)]
+pub struct DespawnBuilding {
+    pub building: Entity,
+}
+
+impl DespawnBuilding {
+    pub fn handle(
+        mut commands: Commands,
+        mut ev_despawn_building: EventReader<DespawnBuilding>,
+        mut q_buildings: Query<&mut Building>,
+        q_ways: Query<&Way>,
+        mut q_units: Query<&mut Unit>,
+    ) {
+        for event in ev_despawn_building.read() {
+            info!(target: "events", "{:?}", event);
+            let Ok(building) = q_buildings.get(event.building) else {
+                continue;
+            };
+            for way_entity in building.ways.clone() {
+                let Ok(way) = q_ways.get(way_entity) else {
+                    continue;
+                };
+                let other = if way.from == event.building { way.to } else { way.from };
+                if let Ok(mut other) = q_buildings.get_mut(other) {
+                    other.ways.retain(|way| *way != way_entity);
+                }
+                commands.entity(way_entity).despawn_recursive();
+            }
+            // units on their way to the building return to where they came from
+            for mut unit in q_units.iter_mut() {
+                if unit.to_building == event.building {
+                    unit.turn_around();
+                }
+            }
+            commands.entity(event.building).despawn_recursive();
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct CustomizeMaterial {}
 
 #[derive(Component, Debug, Default)]
 pub struct Building {
     pub glowing: Glowing,
-    pub connected: Vec<Entity>,
+    /// Entities of the [`Way`]s starting or ending at this building.
+    pub ways: Vec<Entity>,
 }
 
 #[repr(u8)]
@@ -167,6 +290,113 @@ pub enum Glowing {
 }
 
 impl Building {
+    /// How strongly the color of the owning player shows on a building.
+    const TINT_STRENGTH: f32 = 0.4;
+
+    /// Shows the model of the building's definition, or its depleted model once a tree is small.
+    #[allow(clippy::type_complexity)]
+    pub fn insert_scene(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        definitions: Res<Assets<BuildingDefinition>>,
+        q_buildings: Query<
+            (Entity, &Handle<BuildingDefinition>, Option<&Tree>, Option<&Handle<Scene>>),
+            Or<(Changed<Handle<BuildingDefinition>>, Changed<Tree>)>,
+        >,
+    ) {
+        for (entity, definition, tree, scene) in q_buildings.iter() {
+            let Some(definition) = definitions.get(definition) else {
+                continue;
+            };
+            let model = definition
+                .depleted_model()
+                .filter(|_| tree.is_some_and(Tree::is_small))
+                .unwrap_or(&definition.model);
+            let new_scene = asset_server.load(model.to_owned());
+            if scene != Some(&new_scene) {
+                commands.entity(entity).insert((new_scene, VisibilityBundle::default()));
+            }
+        }
+    }
+
+    /// The way connecting this building with `other`, in whichever direction.
+    pub fn way_to(&self, other: Entity, q_ways: &Query<&Way>) -> Option<Entity> {
+        self.ways
+            .iter()
+            .copied()
+            .find(|way| q_ways.get(*way).is_ok_and(|way| way.from == other || way.to == other))
+    }
+
+    /// Buildings units may be sent to from this building, in the order the ways were built.
+    pub fn destinations(&self, entity: Entity, q_ways: &Query<&Way>) -> Vec<Entity> {
+        self.ways.iter().filter_map(|way| q_ways.get(*way).ok()?.destination_from(entity)).collect()
+    }
+
+    /// Ways units may leave this building along, with their weights, in the order the ways were
+    /// built.
+    pub fn outflows(&self, entity: Entity, q_ways: &Query<&Way>) -> Vec<(Entity, u32)> {
+        self.ways
+            .iter()
+            .filter_map(|way_entity| {
+                let way = q_ways.get(*way_entity).ok()?;
+                way.destination_from(entity)?;
+                Some((*way_entity, way.weight))
+            })
+            .collect()
+    }
+
+    /// Fraction of the units leaving this building that each outgoing way gets.
+    pub fn flow_shares(&self, entity: Entity, q_ways: &Query<&Way>) -> Vec<(Entity, f32)> {
+        let outflows = self.outflows(entity, q_ways);
+        let total: u32 = outflows.iter().map(|(_, weight)| weight).sum();
+        outflows
+            .into_iter()
+            .map(|(way, weight)| (way, if total == 0 { 0.0 } else { weight as f32 / total as f32 }))
+            .collect()
+    }
+
+    /// The color of the owning player, transparent for neutral buildings.
+    fn tint(owner: Option<&Owner>, q_players: &Query<&Player>) -> Vec4 {
+        owner.and_then(|owner| q_players.get(owner.0).ok()).map_or(Vec4::ZERO, |player| {
+            Vec4::from(player.color.with_a(Self::TINT_STRENGTH).as_linear_rgba_f32())
+        })
+    }
+
+    /// The primitives of a building's scene, which are two levels below the scene root.
+    fn scene_primitives<'a>(
+        entity: Entity,
+        q_children: &'a Query<&Children>,
+    ) -> Option<&'a Children> {
+        q_children
+            .get(entity)
+            .ok()
+            .and_then(|children| q_children.get(*children.first()?).ok())
+            .and_then(|children| q_children.get(*children.first()?).ok())
+    }
+
+    /// Recolors buildings that changed their owner. Newly spawned buildings are tinted once
+    /// their scene is instanced.
+    #[allow(clippy::type_complexity)]
+    pub fn update_tint(
+        q_buildings: Query<(Entity, Option<&Owner>), (With<Building>, Changed<Owner>)>,
+        q_players: Query<&Player>,
+        q_children: Query<&Children>,
+        q_building_primitives: Query<&Handle<BuildingExtendedMaterial>>,
+        mut materials: ResMut<Assets<BuildingExtendedMaterial>>,
+    ) {
+        for (entity, owner) in q_buildings.iter() {
+            let Some(children) = Self::scene_primitives(entity, &q_children) else {
+                continue;
+            };
+            let tint = Self::tint(owner, &q_players);
+            for material in q_building_primitives.iter_many(children) {
+                if let Some(material) = materials.get_mut(material.id()) {
+                    material.extension.tint = tint;
+                }
+            }
+        }
+    }
+
     pub fn update_glowing(
         mut ev_input: EventReader<InputEvent>,
         mut ev_interact_way: EventReader<InteractWay>,
@@ -178,29 +408,45 @@ impl Building {
     ) {
         let mut modified_buildings = Vec::new();
 
+        // buildings referenced by events may have been despawned in the meantime
+        let mut set_glowing = |entity: Entity, glowing: Glowing, only_if: Option<Glowing>| {
+            if let Ok(mut building) = q_buildings.get_mut(entity) {
+                if only_if.is_none() || only_if == Some(building.glowing) {
+                    building.glowing = glowing;
+                    modified_buildings.push(entity);
+                }
+            }
+        };
+
         for event in ev_interact_way.read() {
             match *event {
                 InteractWay::Start {
                     from,
                 } => {
-                    q_buildings.get_mut(from).unwrap().glowing = Glowing::Connecting;
-                    modified_buildings.push(from);
+                    set_glowing(from, Glowing::Connecting, None);
                 }
                 InteractWay::Finish {
                     from,
                     connect_to,
+                    ..
                 } => {
-                    q_buildings.get_mut(from).unwrap().glowing = Glowing::Off;
-                    modified_buildings.push(from);
-                    q_buildings.get_mut(connect_to).unwrap().glowing = Glowing::Off;
-                    modified_buildings.push(connect_to);
+                    set_glowing(from, Glowing::Off, None);
+                    set_glowing(connect_to, Glowing::Off, None);
                 }
                 InteractWay::Abort {
                     aborted,
                 } => {
-                    q_buildings.get_mut(aborted).unwrap().glowing = Glowing::Off;
-                    modified_buildings.push(aborted);
+                    set_glowing(aborted, Glowing::Off, None);
                 }
+                InteractWay::ToggleDirection {
+                    ..
+                }
+                | InteractWay::Remove {
+                    ..
+                }
+                | InteractWay::SetWeight {
+                    ..
+                } => {}
             };
         }
 
@@ -209,28 +455,21 @@ impl Building {
                 building: entity,
             } = *event
             {
-                let mut building = q_buildings.get_mut(entity).unwrap();
-                if building.glowing == Glowing::Hovering {
-                    building.glowing = Glowing::Off;
-                    modified_buildings.push(entity);
-                }
+                set_glowing(entity, Glowing::Off, Some(Glowing::Hovering));
             }
         }
 
         if let Some(entity) = input_controller.hovering_building {
-            let mut building = q_buildings.get_mut(entity).unwrap();
-            if building.glowing == Glowing::Off {
-                building.glowing = Glowing::Hovering;
-                modified_buildings.push(entity);
-            }
+            set_glowing(entity, Glowing::Hovering, Some(Glowing::Off));
         }
 
         for entity in modified_buildings {
-            let building = q_buildings.get(entity).unwrap();
-            let children = q_children.get(entity).unwrap();
-
-            let children = q_children.get(*children.iter().next().unwrap()).unwrap();
-            let children = q_children.get(*children.iter().next().unwrap()).unwrap();
+            let Ok(building) = q_buildings.get(entity) else {
+                continue;
+            };
+            let Some(children) = Self::scene_primitives(entity, &q_children) else {
+                continue;
+            };
             for child in children.iter() {
                 if let Ok(material) = q_building_primitives.get_mut(*child) {
                     let material = materials.get_mut(material.id()).unwrap();
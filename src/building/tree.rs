@@ -1,86 +1,92 @@
-use bevy::transform::TransformSystem::TransformPropagate;
-use bevy::{math::Affine3A, prelude::*};
-use bevy_xpbd_3d::plugins::collision::Collider;
+use bevy::prelude::*;
 
-use super::Building;
-use crate::unit::{SpawnUnit, Unit, UnitArrived};
+use super::{Building, DespawnBuilding};
+use crate::{
+    economy::{Cargo, ResourceKind},
+    game::SimulationSet,
+    replay::SimulationId,
+    routing::WayGraph,
+    unit::{SpawnUnit, Unit, UnitArrived},
+    unit_kind::UnitKinds,
+    way::Way,
+};
 
 pub struct TreePlugin;
 
 impl Plugin for TreePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, TreeSpawner::setup)
-            .add_event::<SpawnTree>()
-            .add_systems(Update, (SpawnTree::handle, Tree::update))
-            .add_systems(PostUpdate, Tree::unit_arrived.after(TransformPropagate));
-    }
-}
-
-#[derive(Resource, Reflect)]
-pub struct TreeSpawner {
-    pub scene: Handle<Scene>,
-}
-
-impl TreeSpawner {
-    pub fn setup(mut commands: Commands, asset_server: ResMut<AssetServer>) {
-        let scene = asset_server.load("models/detail_treeLarge.glb#Scene0");
-
-        commands.insert_resource(TreeSpawner {
-            scene,
-        });
-    }
-}
-
-#[derive(Event)]
-pub struct SpawnTree {
-    pub position: Vec3,
-}
-
-impl SpawnTree {
-    pub fn handle(
-        mut commands: Commands,
-        head_quaters_spawner: ResMut<TreeSpawner>,
-        mut spawn_head_quarters: EventReader<SpawnTree>,
-    ) {
-        for event in spawn_head_quarters.read() {
-            commands.spawn((
-                Tree {},
-                Building::default(),
-                SceneBundle {
-                    scene: head_quaters_spawner.scene.clone(),
-                    transform: Transform::from_translation(event.position)
-                        .with_scale(Vec3::splat(2.0)),
-                    ..default()
-                },
-                Collider::cuboid(1.0, 1.0, 1.0),
-            ));
-        }
+        app.add_systems(
+            FixedUpdate,
+            Tree::unit_arrived.in_set(SimulationSet::Update).after(Unit::update),
+        );
     }
 }
 
 #[derive(Component)]
-pub struct Tree {}
+pub struct Tree {
+    /// Wood left to harvest, the tree is despawned when it runs out.
+    pub wood: u32,
+    pub initial_wood: u32,
+}
 
 impl Tree {
-    pub fn update(time: Res<Time>, mut trees: Query<(Entity, &mut Tree, &Building, &Transform)>) {}
+    pub fn is_small(&self) -> bool {
+        self.wood * 2 <= self.initial_wood
+    }
 
+    /// Empty units harvest as much wood as they can carry and bring it back home, or along the
+    /// first way leaving the tree if their home can't be reached from here. Units that can't
+    /// carry anything, or find no wood left, go the same way back empty.
     pub fn unit_arrived(
-        mut commands: Commands,
         mut ev_unit_arrived: EventReader<UnitArrived>,
-        mut trees: Query<(&mut Tree, &mut GlobalTransform)>,
+        mut ev_spawn_unit: EventWriter<SpawnUnit>,
+        mut ev_despawn_building: EventWriter<DespawnBuilding>,
+        mut trees: Query<(&mut Tree, &Building)>,
+        q_ways: Query<&Way>,
+        q_way_graph: Query<(Entity, &Way, &SimulationId)>,
+        unit_kinds: Res<UnitKinds>,
     ) {
-        //for event in ev_unit_arrived.read() {
-        //let (tree, mut global_transform) = trees.get_mut(event.building).unwrap();
-        for (tree, mut global_transform) in trees.iter_mut() {
-            let shearing_y = 10.5;
-            let shearing_z = 10.5;
-            let shear_affine = Affine3A::from_cols_array_2d(&[
-                [1.0, shearing_y, shearing_z],
-                [0.0, 1.0, 0.0],
-                [0.0, 0.0, 1.0],
-                [0.0, 0.0, 0.0],
-            ]);
-            *global_transform = GlobalTransform::from(global_transform.affine() * shear_affine);
+        let graph = WayGraph::new(q_way_graph.iter());
+        for event in ev_unit_arrived.read() {
+            if event.unit.cargo.is_some() {
+                continue;
+            }
+            let Ok((mut tree, building)) = trees.get_mut(event.building) else {
+                continue;
+            };
+            let home = event.unit.home;
+            let Some(destination) = graph
+                .shortest_path(event.building, home)
+                .filter(|route| !route.is_empty())
+                .map(|_| home)
+                .or_else(|| building.destinations(event.building, &q_ways).first().copied())
+            else {
+                // the arriving unit is already gone, so at least tell why nothing comes back
+                warn!(
+                    "{:?} of {:?} has no way to leave tree {:?} and is lost",
+                    event.unit.kind, event.unit.home, event.building
+                );
+                continue;
+            };
+
+            let amount = unit_kinds.get(event.unit.kind).capacity.min(tree.wood);
+            tree.wood -= amount;
+            ev_spawn_unit.send(SpawnUnit {
+                kind: event.unit.kind,
+                from_building: event.building,
+                destination,
+                home,
+                cargo: (amount > 0).then_some(Cargo {
+                    kind: ResourceKind::Wood,
+                    amount,
+                }),
+            });
+
+            if amount > 0 && tree.wood == 0 {
+                ev_despawn_building.send(DespawnBuilding {
+                    building: event.building,
+                });
+            }
         }
     }
 }
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    building::{construction::UnderConstruction, Building, DespawnBuilding},
+    game::SimulationSet,
+    player::{Owner, Player},
+    replay::SimulationId,
+    unit::{Unit, UnitArrived},
+    unit_kind::UnitKinds,
+    way::{InteractWay, Way},
+};
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CombatRound>().add_event::<BuildingCaptured>().add_systems(
+            FixedUpdate,
+            (
+                CombatRound::update.in_set(SimulationSet::Update).after(Unit::update),
+                (Garrison::unit_arrived, BuildingCaptured::handle)
+                    .chain()
+                    .in_set(SimulationSet::Update)
+                    .after(Unit::update),
+            ),
+        );
+    }
+}
+
+/// Enemy units within [`CombatRound::RANGE`] of each other fight, dealing their attack all at
+/// once at the end of every round.
+#[derive(Resource, Debug)]
+pub struct CombatRound {
+    pub timer: Timer,
+}
+
+impl Default for CombatRound {
+    fn default() -> Self {
+        CombatRound {
+            timer: Timer::from_seconds(Self::SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl CombatRound {
+    pub const SECONDS: f32 = 1.0;
+    /// Distance at which units start fighting, whether they walk on the same way or not.
+    pub const RANGE: f32 = 1.0;
+
+    /// Lets every unit that can fight attack the closest enemy in range. Units that can't fight
+    /// keep walking, even while they are attacked.
+    pub fn update(
+        mut commands: Commands,
+        time: Res<Time>,
+        mut round: ResMut<CombatRound>,
+        mut q_units: Query<(Entity, &mut Unit, &Transform, &Owner, &SimulationId)>,
+        q_players: Query<&Player>,
+        unit_kinds: Res<UnitKinds>,
+    ) {
+        round.timer.tick(time.delta());
+        // in a fixed order, so ties between targets are broken the same way in every run
+        let mut units: Vec<_> = q_units
+            .iter()
+            .map(|(entity, unit, transform, owner, id)| {
+                (entity, transform.translation, *owner, unit_kinds.get(unit.kind).attack, *id)
+            })
+            .collect();
+        units.sort_by_key(|(.., id)| *id);
+
+        let mut damage = BTreeMap::new();
+        for (entity, position, owner, attack, _) in &units {
+            let target = units
+                .iter()
+                .filter(|(_, other_position, other_owner, ..)| {
+                    *attack > 0
+                        && owner.is_enemy_of(other_owner, &q_players)
+                        && position.distance(*other_position) <= Self::RANGE
+                })
+                .min_by(|(_, a, ..), (_, b, ..)| {
+                    position.distance(*a).total_cmp(&position.distance(*b))
+                })
+                .map(|(target, ..)| *target);
+            if let Some(target) = target {
+                if round.timer.just_finished() {
+                    *damage.entry(target).or_insert(0) += attack;
+                }
+            }
+            q_units.get_mut(*entity).unwrap().1.fighting = target;
+        }
+
+        let mut killed = Vec::new();
+        for (entity, damage) in damage {
+            let (_, mut unit, ..) = q_units.get_mut(entity).unwrap();
+            unit.health = unit.health.saturating_sub(damage);
+            if unit.health == 0 {
+                info!("unit {:?} was killed", entity);
+                commands.entity(entity).despawn_recursive();
+                killed.push(entity);
+            }
+        }
+        for (_, mut unit, ..) in q_units.iter_mut() {
+            if unit.fighting.is_some_and(|target| killed.contains(&target)) {
+                unit.fighting = None;
+            }
+        }
+    }
+}
+
+/// Damage a building can take before it is destroyed.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+/// Units of other players that still have to arrive at a building to capture it.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Control {
+    pub points: u32,
+    pub max: u32,
+}
+
+/// Fighters staying in a building to defend it.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Garrison {
+    pub units: u32,
+    pub capacity: u32,
+}
+
+impl Garrison {
+    /// Units arriving empty at an enemy or neutral building are stopped by one of its garrison,
+    /// who only falls as well if the unit can fight. Without a garrison, every unit takes a
+    /// [`Control`] point and fighters damage buildings that can't be captured. Fighters arriving
+    /// at their own finished building join its garrison while there is room.
+    #[allow(clippy::type_complexity)]
+    pub fn unit_arrived(
+        mut commands: Commands,
+        mut ev_unit_arrived: EventReader<UnitArrived>,
+        mut ev_despawn_building: EventWriter<DespawnBuilding>,
+        mut ev_building_captured: EventWriter<BuildingCaptured>,
+        mut q_buildings: Query<
+            (
+                Option<&Owner>,
+                Option<&mut Health>,
+                Option<&mut Garrison>,
+                Option<&mut Control>,
+                Has<UnderConstruction>,
+            ),
+            With<Building>,
+        >,
+        q_players: Query<&Player>,
+        unit_kinds: Res<UnitKinds>,
+    ) {
+        for event in ev_unit_arrived.read() {
+            let attack = unit_kinds.get(event.unit.kind).attack;
+            let Some(unit_owner) = event.owner else {
+                continue;
+            };
+            if event.unit.cargo.is_some() {
+                continue;
+            }
+            let Ok((owner, health, garrison, control, under_construction)) =
+                q_buildings.get_mut(event.building)
+            else {
+                continue;
+            };
+            let hostile = match owner {
+                Some(owner) => unit_owner.is_enemy_of(owner, &q_players),
+                // neutral buildings can be captured by anyone
+                None => true,
+            };
+
+            if hostile {
+                if let Some(mut garrison) = garrison.filter(|garrison| garrison.units > 0) {
+                    if attack > 0 {
+                        garrison.units -= 1;
+                    }
+                } else if let Some(mut control) = control {
+                    // several units may arrive in the same tick
+                    if control.points == 0 {
+                        continue;
+                    }
+                    control.points -= 1;
+                    if control.points == 0 {
+                        control.points = control.max;
+                        commands.entity(event.building).insert(unit_owner);
+                        ev_building_captured.send(BuildingCaptured {
+                            building: event.building,
+                            previous: owner.map(|owner| owner.0),
+                            owner: unit_owner.0,
+                        });
+                    }
+                } else if let Some(mut health) =
+                    health.filter(|health| attack > 0 && health.current > 0)
+                {
+                    health.current = health.current.saturating_sub(attack);
+                    if health.current == 0 {
+                        info!("building {:?} was destroyed", event.building);
+                        ev_despawn_building.send(DespawnBuilding {
+                            building: event.building,
+                        });
+                    }
+                }
+            } else if owner == Some(&unit_owner) && attack > 0 && !under_construction {
+                if let Some(mut garrison) =
+                    garrison.filter(|garrison| garrison.units < garrison.capacity)
+                {
+                    garrison.units += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Sent when a building changed hands because its [`Control`] points ran out.
+#[derive(Event, Debug)]
+pub struct BuildingCaptured {
+    pub building: Entity,
+    /// The [`Player`] entity who lost the building, `None` if it was neutral.
+    pub previous: Option<Entity>,
+    pub owner: Entity,
+}
+
+impl BuildingCaptured {
+    /// Hands the ways of captured buildings to the new owner if they lead to one of its or its
+    /// allies' buildings or to a neutral one, and removes the ways leading to enemies.
+    pub fn handle(
+        mut commands: Commands,
+        mut ev_building_captured: EventReader<BuildingCaptured>,
+        mut ev_interact_way: EventWriter<InteractWay>,
+        q_buildings: Query<&Building>,
+        q_ways: Query<&Way>,
+        q_owners: Query<&Owner>,
+        q_players: Query<&Player>,
+    ) {
+        for event in ev_building_captured.read() {
+            info!(target: "events", "{:?}", event);
+            let Ok(building) = q_buildings.get(event.building) else {
+                continue;
+            };
+            let owner = Owner(event.owner);
+            for way_entity in &building.ways {
+                let Ok(way) = q_ways.get(*way_entity) else {
+                    continue;
+                };
+                let other = if way.from == event.building { way.to } else { way.from };
+                if q_owners.get(other).is_ok_and(|other| owner.is_enemy_of(other, &q_players)) {
+                    ev_interact_way.send(InteractWay::Remove {
+                        way: *way_entity,
+                    });
+                } else {
+                    commands.entity(*way_entity).insert(owner);
+                }
+            }
+        }
+    }
+}
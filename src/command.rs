@@ -0,0 +1,237 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    building::{construction::PlaceBuilding, headquarters::SelectUnitKind, Building},
+    game::SimulationSet,
+    placement::Placement,
+    player::{Owner, Players},
+    unit_kind::UnitKind,
+    way::{InteractWay, Way, WayPath},
+};
+
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerCommand>().add_systems(
+            FixedUpdate,
+            PlayerCommand::handle
+                .in_set(SimulationSet::Interact)
+                .before(InteractWay::handle)
+                .before(PlaceBuilding::handle)
+                .before(SelectUnitKind::handle),
+        );
+    }
+}
+
+/// Everything a player can do in a match, no matter if a human clicked, an
+/// [`Ai`](crate::ai::Ai) decided or a replay recorded it.
+///
+/// Buildings and ways are referred to by `E`, which is their [`Entity`] in the running world and
+/// their [`SimulationId`](crate::replay::SimulationId) in replays.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameCommand<E = Entity> {
+    /// Builds a way from one of the player's buildings to any other building, as long as it only
+    /// crosses walkable tiles and touches no other building.
+    ConnectBuildings {
+        from: E,
+        to: E,
+        /// Ground points between the buildings the way bends through.
+        waypoints: Vec<Vec3>,
+    },
+    RemoveWay {
+        way: E,
+    },
+    ToggleWayDirection {
+        way: E,
+    },
+    SetFlowWeight {
+        way: E,
+        weight: u32,
+    },
+    /// Starts constructing a building, paid from the player's stockpiles.
+    PlaceBuilding {
+        definition: String,
+        position: Vec3,
+    },
+    SelectUnitKind {
+        building: E,
+        kind: UnitKind,
+    },
+}
+
+impl<E> GameCommand<E> {
+    /// The same command referring to buildings and ways by `F`, `None` if any of them can't be
+    /// mapped.
+    pub fn map<F>(self, mut f: impl FnMut(E) -> Option<F>) -> Option<GameCommand<F>> {
+        Some(match self {
+            GameCommand::ConnectBuildings {
+                from,
+                to,
+                waypoints,
+            } => GameCommand::ConnectBuildings {
+                from: f(from)?,
+                to: f(to)?,
+                waypoints,
+            },
+            GameCommand::RemoveWay {
+                way,
+            } => GameCommand::RemoveWay {
+                way: f(way)?,
+            },
+            GameCommand::ToggleWayDirection {
+                way,
+            } => GameCommand::ToggleWayDirection {
+                way: f(way)?,
+            },
+            GameCommand::SetFlowWeight {
+                way,
+                weight,
+            } => GameCommand::SetFlowWeight {
+                way: f(way)?,
+                weight,
+            },
+            GameCommand::PlaceBuilding {
+                definition,
+                position,
+            } => GameCommand::PlaceBuilding {
+                definition,
+                position,
+            },
+            GameCommand::SelectUnitKind {
+                building,
+                kind,
+            } => GameCommand::SelectUnitKind {
+                building: f(building)?,
+                kind,
+            },
+        })
+    }
+}
+
+/// A [`GameCommand`] issued by the player at `player` in [`Players`].
+#[derive(Event, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerCommand {
+    pub player: usize,
+    pub command: GameCommand,
+}
+
+impl PlayerCommand {
+    /// The only place deciding whether players may do what they ask for. Accepted commands are
+    /// passed on to the systems carrying them out, everything else is dropped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn handle(
+        mut ev_player_command: EventReader<PlayerCommand>,
+        mut ev_interact_way: EventWriter<InteractWay>,
+        mut ev_place_building: EventWriter<PlaceBuilding>,
+        mut ev_select_unit_kind: EventWriter<SelectUnitKind>,
+        players: Res<Players>,
+        q_buildings: Query<(&Building, &Transform)>,
+        q_ways: Query<&Way>,
+        q_owners: Query<&Owner>,
+        placement: Placement,
+    ) {
+        for event in ev_player_command.read() {
+            info!(target: "events", "{:?}", event);
+            // ways may lead to any building, but only start at and be changed by their owner
+            let controls =
+                |entity: Entity| players.controls(event.player, q_owners.get(entity).ok());
+            let accepted = match event.command {
+                GameCommand::ConnectBuildings {
+                    from,
+                    to,
+                    ref waypoints,
+                } => {
+                    // fails for `from == to` as well
+                    let ends = q_buildings.get_many([from, to]);
+                    let accepted = controls(from)
+                        && ends.is_ok_and(|[(from_building, from_transform), (_, to_transform)]| {
+                            let path = WayPath::curved(
+                                from_transform.translation,
+                                waypoints,
+                                to_transform.translation,
+                            );
+                            from_building.way_to(to, &q_ways).is_none()
+                                && placement.is_way_clear(&path, &[from, to])
+                        });
+                    if accepted {
+                        ev_interact_way.send(InteractWay::Finish {
+                            from,
+                            connect_to: to,
+                            waypoints: waypoints.clone(),
+                        });
+                    }
+                    accepted
+                }
+                GameCommand::RemoveWay {
+                    way,
+                } => {
+                    let accepted = q_ways.contains(way) && controls(way);
+                    if accepted {
+                        ev_interact_way.send(InteractWay::Remove {
+                            way,
+                        });
+                    }
+                    accepted
+                }
+                GameCommand::ToggleWayDirection {
+                    way,
+                } => {
+                    let accepted = q_ways.contains(way) && controls(way);
+                    if accepted {
+                        ev_interact_way.send(InteractWay::ToggleDirection {
+                            way,
+                        });
+                    }
+                    accepted
+                }
+                GameCommand::SetFlowWeight {
+                    way,
+                    weight,
+                } => {
+                    let accepted =
+                        q_ways.contains(way) && controls(way) && weight <= Way::MAX_WEIGHT;
+                    if accepted {
+                        ev_interact_way.send(InteractWay::SetWeight {
+                            way,
+                            weight,
+                        });
+                    }
+                    accepted
+                }
+                GameCommand::PlaceBuilding {
+                    ref definition,
+                    position,
+                } => {
+                    // without any players, like in an empty world, neutral stockpiles pay
+                    let accepted = players.0.is_empty() || players.get(event.player).is_some();
+                    if accepted {
+                        ev_place_building.send(PlaceBuilding {
+                            definition: definition.clone(),
+                            position,
+                            owner: players.get(event.player),
+                        });
+                    }
+                    accepted
+                }
+                GameCommand::SelectUnitKind {
+                    building,
+                    kind,
+                } => {
+                    let accepted = q_buildings.contains(building) && controls(building);
+                    if accepted {
+                        ev_select_unit_kind.send(SelectUnitKind {
+                            building,
+                            kind,
+                        });
+                    }
+                    accepted
+                }
+            };
+            if !accepted {
+                warn!("rejected {:?}", event);
+            }
+        }
+    }
+}
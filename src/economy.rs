@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    game::SimulationSet,
+    unit::{Unit, UnitArrived},
+};
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            Stockpile::receive_cargo.in_set(SimulationSet::Update).after(Unit::update),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect, Deserialize)]
+pub enum ResourceKind {
+    Wood,
+    Crystal,
+}
+
+/// Resources carried by a unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cargo {
+    pub kind: ResourceKind,
+    pub amount: u32,
+}
+
+/// Resources stored in a building, filled by units arriving with [`Cargo`].
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Stockpile {
+    amounts: BTreeMap<ResourceKind, u32>,
+}
+
+impl Stockpile {
+    pub fn amount(&self, kind: ResourceKind) -> u32 {
+        self.amounts.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, cargo: Cargo) {
+        *self.amounts.entry(cargo.kind).or_insert(0) += cargo.amount;
+    }
+
+    /// Removes up to `amount` of `kind` and returns how much was actually taken.
+    pub fn take(&mut self, kind: ResourceKind, amount: u32) -> u32 {
+        let stored = self.amounts.entry(kind).or_insert(0);
+        let taken = amount.min(*stored);
+        *stored -= taken;
+        taken
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ResourceKind, u32)> + '_ {
+        self.amounts.iter().map(|(kind, amount)| (*kind, *amount))
+    }
+
+    pub fn receive_cargo(
+        mut ev_unit_arrived: EventReader<UnitArrived>,
+        mut q_stockpiles: Query<&mut Stockpile>,
+    ) {
+        for event in ev_unit_arrived.read() {
+            let Some(cargo) = event.unit.cargo else {
+                continue;
+            };
+            if let Ok(mut stockpile) = q_stockpiles.get_mut(event.building) {
+                stockpile.add(cargo);
+            }
+        }
+    }
+}
@@ -1,51 +1,419 @@
-use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+use bevy::{ecs::schedule::ExecutorKind, prelude::*};
 use bevy_xpbd_3d::prelude::*;
 
 use crate::{
-    building::{headquarters::SpawnHeadQuarters, tree::SpawnTree, BuildingPlugins},
-    input::InputPlugin,
-    unit::UnitPlugin,
-    way::WayPlugin,
+    ai::{AiPlugin, Difficulty},
+    building::{
+        construction::UnderConstruction,
+        definition::{BuildingDefinition, BuildingDefinitions},
+        Building, BuildingPlugins, BuildingVisualsPlugin,
+    },
+    combat::{CombatPlugin, CombatRound},
+    command::CommandPlugin,
+    economy::{EconomyPlugin, ResourceKind, Stockpile},
+    input::{InputEvent, InputPlugin},
+    map::{CurrentMap, Map, MapPlugin, MapSource, WinCondition},
+    player::{Owner, Player, PlayerPlugin, Players, Team},
+    replay::{NextSimulationId, ReplayControlsPlugin, ReplayPlugin},
+    rng::SimulationRng,
+    terrain::{Terrain, TerrainPlugin, TerrainVisualsPlugin},
+    unit::{Unit, UnitPlugin, UnitVisualsPlugin, WaitingUnits},
+    way::{Way, WayPlugin, WayVisualsPlugin},
 };
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, Game::setup).add_plugins((
+        app.add_plugins((
+            SimulationPlugin,
+            InputPlugin,
+            TerrainVisualsPlugin,
+            WayVisualsPlugin,
+            BuildingVisualsPlugin,
+            UnitVisualsPlugin,
+            ReplayControlsPlugin,
+        ))
+        .add_systems(Startup, MatchScreen::setup)
+        .add_systems(
+            Update,
+            (Game::handle_input, MatchScreen::update.run_if(state_changed::<MatchState>)),
+        );
+    }
+}
+
+/// Game rules only, without rendering, input or loaded assets, so it can run headless.
+///
+/// All gameplay systems run in [`FixedUpdate`] at [`SimulationSettings::tick_rate`], so the same
+/// events always produce the same world state, independent of the frame rate. The simulation only
+/// advances while [`MatchState::Playing`], which is entered once the [`BuildingDefinitions`] and
+/// the [`CurrentMap`] are loaded.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = app.world.get_resource::<SimulationSettings>().cloned().unwrap_or_default();
+
+        app.insert_resource(Time::<Fixed>::from_hz(settings.tick_rate))
+            .insert_resource(SimulationRng::new(settings.seed))
+            .insert_resource(settings)
+            .init_resource::<SimulationTick>()
+            .init_resource::<Game>()
+            .init_state::<MatchState>()
+            .add_event::<RestartMatch>()
+            .configure_sets(
+                FixedUpdate,
+                (SimulationSet::Spawn, SimulationSet::Interact, SimulationSet::Update)
+                    .chain()
+                    .run_if(in_state(MatchState::Playing)),
+            )
+            // parallel systems would reserve entity ids in a nondeterministic order
+            .edit_schedule(FixedUpdate, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            })
+            .add_systems(FixedFirst, SimulationTick::advance.run_if(in_state(MatchState::Playing)))
+            .add_systems(
+                PreUpdate,
+                Game::finish_loading.after(BuildingDefinitions::update).run_if(
+                    in_state(MatchState::Loading)
+                        .and_then(BuildingDefinitions::loaded)
+                        .and_then(CurrentMap::loaded),
+                ),
+            )
+            .add_systems(Update, Game::restart)
+            .add_systems(FixedUpdate, Game::update.in_set(SimulationSet::Update));
+
+        app.add_plugins((
             PhysicsPlugins::default(),
             //PhysicsDebugPlugin::default(),
-            InputPlugin,
             WayPlugin,
             BuildingPlugins.build(),
             UnitPlugin,
+            CombatPlugin,
+            CommandPlugin,
+            EconomyPlugin,
+            MapPlugin,
+            PlayerPlugin,
+            TerrainPlugin,
+            AiPlugin,
+            ReplayPlugin,
         ));
     }
 }
 
-#[derive(Resource)]
-pub struct Game {}
+#[derive(Resource, Clone, Debug)]
+pub struct SimulationSettings {
+    /// Simulation ticks per second.
+    pub tick_rate: f64,
+    pub seed: u64,
+    /// The [`Map`] to start with, or an empty world without one.
+    pub map: Option<MapSource>,
+    /// Whether to wait in [`MatchState::Lobby`] for the players to start the match.
+    pub lobby: bool,
+    /// Difficulty of the [`Ai`](crate::ai::Ai) playing the player at each index, the others are
+    /// left to the input.
+    pub ai: BTreeMap<usize, Difficulty>,
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        SimulationSettings {
+            tick_rate: 60.0,
+            seed: 0,
+            map: None,
+            lobby: false,
+            ai: BTreeMap::new(),
+        }
+    }
+}
+
+/// Number of simulation ticks since the start of the match.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SimulationTick(pub u64);
+
+impl SimulationTick {
+    pub fn advance(mut tick: ResMut<SimulationTick>) {
+        tick.0 += 1;
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimulationSet {
+    /// Spawns the entities requested by spawn events.
+    Spawn,
+    /// Applies interactions of players, like connecting buildings.
+    Interact,
+    /// Advances buildings and units.
+    Update,
+}
+
+/// Phases of a match, the simulation only advances while [`MatchState::Playing`].
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MatchState {
+    /// Waits for the building definitions and the map.
+    #[default]
+    Loading,
+    /// Waits for the players to start, see [`SimulationSettings::lobby`].
+    Lobby,
+    Playing,
+    Paused,
+    /// A team fulfilled one of the map's [`WinCondition`]s, or the match ended in a draw.
+    GameOver,
+}
+
+/// Ends the current match and starts its map again from the beginning.
+#[derive(Event, Debug)]
+pub struct RestartMatch;
+
+/// Progress of the match towards the map's [`WinCondition`]s.
+#[derive(Resource, Debug, Default)]
+pub struct Game {
+    /// The [`Team`] entity that won the match.
+    pub winner: Option<Entity>,
+    /// Whether the match ended without a winner, as the last head quarters of all teams fell in
+    /// the same tick.
+    pub draw: bool,
+    /// Seconds each team has been holding enough buildings, by the index of the
+    /// [`WinCondition::HoldBuildings`] condition and the team.
+    holding: BTreeMap<(usize, Entity), f32>,
+}
+
+/// What a team has at the moment, counting only finished buildings.
+#[derive(Debug, Default)]
+struct TeamStatus {
+    buildings: u32,
+    head_quarters: u32,
+    resources: BTreeMap<ResourceKind, u32>,
+}
 
 impl Game {
-    pub fn setup(
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some() || self.draw
+    }
+
+    /// Leaves [`MatchState::Loading`] once the simulation has everything it needs.
+    pub fn finish_loading(
+        settings: Res<SimulationSettings>,
+        mut next_state: ResMut<NextState<MatchState>>,
+    ) {
+        next_state.set(if settings.lobby { MatchState::Lobby } else { MatchState::Playing });
+    }
+
+    /// Ends the match as soon as a team fulfills any of the map's win conditions, or in a draw
+    /// once no team is left standing to win by destroying the other head quarters.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn update(
+        time: Res<Time>,
+        mut game: ResMut<Game>,
+        current_map: Res<CurrentMap>,
+        maps: Res<Assets<Map>>,
+        definitions: Res<BuildingDefinitions>,
+        q_players: Query<&Player>,
+        q_teams: Query<&Team>,
+        q_buildings: Query<
+            (&Owner, &Handle<BuildingDefinition>, Option<&Stockpile>),
+            (With<Building>, Without<UnderConstruction>),
+        >,
+        mut next_state: ResMut<NextState<MatchState>>,
+    ) {
+        let Some(map) = current_map.handle.as_ref().and_then(|handle| maps.get(handle)) else {
+            return;
+        };
+        let head_quarters = definitions.get(BuildingDefinitions::HEAD_QUARTERS);
+        // by team number, so ties between teams are broken the same way in every run
+        let number = |team: Entity| q_teams.get(team).map_or(u32::MAX, |team| team.number);
+        let mut teams: BTreeMap<(u32, Entity), TeamStatus> = q_players
+            .iter()
+            .map(|player| ((number(player.team), player.team), TeamStatus::default()))
+            .collect();
+        for (owner, definition, stockpile) in q_buildings.iter() {
+            let Some(status) = q_players
+                .get(owner.0)
+                .ok()
+                .and_then(|player| teams.get_mut(&(number(player.team), player.team)))
+            else {
+                continue;
+            };
+            status.buildings += 1;
+            if Some(definition) == head_quarters {
+                status.head_quarters += 1;
+            }
+            for (kind, amount) in stockpile.into_iter().flat_map(Stockpile::iter) {
+                *status.resources.entry(kind).or_insert(0) += amount;
+            }
+        }
+        let standing = teams.values().filter(|status| status.head_quarters > 0).count();
+
+        // every condition is checked for every team, so the timers of all of them advance
+        let mut winner = None;
+        for (index, condition) in map.win_conditions.iter().enumerate() {
+            for ((_, team), status) in &teams {
+                let fulfilled = match *condition {
+                    WinCondition::DestroyHeadQuarters => {
+                        teams.len() > 1 && standing == 1 && status.head_quarters > 0
+                    }
+                    WinCondition::HoldBuildings {
+                        count,
+                        seconds,
+                    } => {
+                        let held = game.holding.entry((index, *team)).or_insert(0.0);
+                        if status.buildings >= count {
+                            *held += time.delta_seconds();
+                        } else {
+                            *held = 0.0;
+                        }
+                        *held >= seconds
+                    }
+                    WinCondition::Resources {
+                        kind,
+                        amount,
+                    } => status.resources.get(&kind).is_some_and(|stored| *stored >= amount),
+                };
+                if fulfilled && winner.is_none() {
+                    winner = Some(*team);
+                }
+            }
+        }
+
+        if let Some(winner) = winner {
+            info!("team {:?} won the match", winner);
+            game.winner = Some(winner);
+            next_state.set(MatchState::GameOver);
+        } else if teams.len() > 1
+            && standing == 0
+            && map.win_conditions.contains(&WinCondition::DestroyHeadQuarters)
+        {
+            info!("the match ended in a draw");
+            game.draw = true;
+            next_state.set(MatchState::GameOver);
+        }
+    }
+
+    /// Despawns everything the match spawned and resets the simulation, so the map is spawned
+    /// again in the first tick after loading.
+    #[allow(clippy::type_complexity)]
+    pub fn restart(
         mut commands: Commands,
-        mut ev_spawn_head_quarters: EventWriter<SpawnHeadQuarters>,
-        mut ev_spawn_tree: EventWriter<SpawnTree>,
+        mut ev_restart_match: EventReader<RestartMatch>,
+        q_match_entities: Query<
+            Entity,
+            Or<(With<Building>, With<Way>, With<Unit>, With<Player>, With<Team>)>,
+        >,
+        settings: Res<SimulationSettings>,
+        mut current_map: ResMut<CurrentMap>,
+        mut next_state: ResMut<NextState<MatchState>>,
+    ) {
+        let Some(event) = ev_restart_match.read().last() else {
+            return;
+        };
+        info!(target: "events", "{:?}", event);
+        for entity in q_match_entities.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        commands.insert_resource(SimulationTick::default());
+        commands.insert_resource(SimulationRng::new(settings.seed));
+        commands.insert_resource(CombatRound::default());
+        commands.insert_resource(Players::default());
+        commands.insert_resource(Terrain::default());
+        commands.insert_resource(Game::default());
+        commands.insert_resource(WaitingUnits::default());
+        commands.insert_resource(NextSimulationId::default());
+        current_map.respawn();
+        next_state.set(MatchState::Loading);
+    }
+
+    /// Starts, pauses and restarts the match on the player's request.
+    pub fn handle_input(
+        mut ev_input: EventReader<InputEvent>,
+        mut ev_restart_match: EventWriter<RestartMatch>,
+        state: Res<State<MatchState>>,
+        mut next_state: ResMut<NextState<MatchState>>,
+    ) {
+        for event in ev_input.read() {
+            match (event, state.get()) {
+                (InputEvent::StartMatch, MatchState::Lobby) => {
+                    next_state.set(MatchState::Playing);
+                }
+                (InputEvent::TogglePause, MatchState::Playing) => {
+                    next_state.set(MatchState::Paused);
+                }
+                (InputEvent::TogglePause, MatchState::Paused) => {
+                    next_state.set(MatchState::Playing);
+                }
+                (InputEvent::RestartMatch, MatchState::Paused | MatchState::GameOver) => {
+                    ev_restart_match.send(RestartMatch);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Text in the middle of the screen telling the players what the match is waiting for.
+#[derive(Component)]
+pub struct MatchScreen;
+
+impl MatchScreen {
+    pub fn setup(mut commands: Commands) {
+        commands
+            .spawn(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn((
+                    MatchScreen,
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 40.0,
+                            ..default()
+                        },
+                    )
+                    .with_text_justify(JustifyText::Center),
+                ));
+            });
+    }
+
+    pub fn update(
+        state: Res<State<MatchState>>,
+        game: Res<Game>,
+        current_map: Res<CurrentMap>,
+        maps: Res<Assets<Map>>,
+        q_players: Query<&Player>,
+        mut q_screen: Query<&mut Text, With<MatchScreen>>,
     ) {
-        commands.insert_resource(Game {});
-        ev_spawn_head_quarters.send(SpawnHeadQuarters {
-            position: Vec3::new(0.0, 0.0, 5.0),
-        });
-        ev_spawn_tree.send(SpawnTree {
-            position: Vec3::new(5.0, 0.0, 0.0),
-        });
-        ev_spawn_tree.send(SpawnTree {
-            position: Vec3::new(-9.0, 0.0, -14.0),
-        });
-        ev_spawn_tree.send(SpawnTree {
-            position: Vec3::new(-6.0, 0.0, -8.0),
-        });
-    }
-
-    pub fn update(mut commands: Commands, time: Res<Time>, mut game: ResMut<Game>) {}
+        let Ok(mut text) = q_screen.get_single_mut() else {
+            return;
+        };
+        let map_name = current_map
+            .handle
+            .as_ref()
+            .and_then(|handle| maps.get(handle))
+            .map_or("Empty world", |map| map.name.as_str());
+        text.sections[0].value = match state.get() {
+            MatchState::Loading => "Loading...".to_owned(),
+            MatchState::Lobby => format!("{map_name}\nPress Enter to start"),
+            MatchState::Playing => String::new(),
+            MatchState::Paused => "Paused\nPress P to continue or R to restart".to_owned(),
+            MatchState::GameOver if game.draw => "Draw!\nPress R to restart".to_owned(),
+            MatchState::GameOver => {
+                let winners: Vec<_> = q_players
+                    .iter()
+                    .filter(|player| Some(player.team) == game.winner)
+                    .map(|player| player.name.as_str())
+                    .collect();
+                format!("{} won!\nPress R to restart", winners.join(" and "))
+            }
+        };
+    }
 }
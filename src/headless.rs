@@ -0,0 +1,21 @@
+use std::time::Duration;
+
+use bevy::{app::ScheduleRunnerPlugin, prelude::*, scene::ScenePlugin};
+
+/// Replaces `DefaultPlugins` when running the [`SimulationPlugin`](crate::game::SimulationPlugin)
+/// without a window or GPU, e.g. on CI or dedicated servers.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / 60.0))),
+            TransformPlugin,
+            HierarchyPlugin,
+            // the physics collider backend expects mesh and scene assets to exist
+            bevy::asset::AssetPlugin::default(),
+            ScenePlugin,
+        ))
+        .init_asset::<Mesh>();
+    }
+}
@@ -1,6 +1,13 @@
 use bevy::prelude::*;
 
-use crate::building::Building;
+use crate::{
+    building::{construction::BuildMode, headquarters::HeadQuarters, Building},
+    command::{GameCommand, PlayerCommand},
+    player::LocalPlayer,
+    replay::ReplayPlayback,
+    terrain::Terrain,
+    way::{Way, WayController},
+};
 
 pub struct InputPlugin;
 
@@ -8,7 +15,11 @@ impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<InputController>()
             .add_event::<InputEvent>()
-            .add_systems(PreUpdate, update);
+            .add_systems(PreUpdate, update)
+            .add_systems(
+                Update,
+                issue_commands.before(WayController::handle_input).before(BuildMode::handle_input),
+            );
     }
 }
 
@@ -16,32 +27,99 @@ impl Plugin for InputPlugin {
 pub struct InputController {
     pub plane_position: Option<Vec3>,
     pub hovering_building: Option<Entity>,
+    /// Only set while no building is hovered.
+    pub hovering_way: Option<Entity>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn update(
     mut controller: ResMut<InputController>,
     buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
     mut ev_input: EventWriter<InputEvent>,
     q_camera: Query<(&Camera, &GlobalTransform)>,
     q_window: Query<&Window>,
     q_buildings: Query<(Entity, &Transform), With<Building>>,
+    q_ways: Query<(Entity, &Way)>,
 ) {
     if buttons.just_pressed(MouseButton::Left) {
         if let Some(hovering_building) = controller.hovering_building {
             ev_input.send(InputEvent::ClickedOnBuilding {
                 building: hovering_building,
             });
-
-            //if let Some(plane_position) = controller.plane_position {
-            //    start_way_evs.send(StartWay {
-            //        from: plane_position,
-            //    });
-            //}
+        } else if let Some(plane_position) = controller.plane_position {
+            ev_input.send(InputEvent::ClickedOnGround {
+                position: plane_position,
+            });
         }
     }
     if buttons.just_pressed(MouseButton::Right) {
         ev_input.send(InputEvent::Abort);
     }
+    if buttons.just_pressed(MouseButton::Right) || keys.just_pressed(KeyCode::Delete) {
+        if let Some(hovering_way) = controller.hovering_way {
+            ev_input.send(InputEvent::RemoveWay {
+                way: hovering_way,
+            });
+        }
+    }
+    let weight_delta = if keys.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        1
+    } else if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        -1
+    } else {
+        0
+    };
+    if weight_delta != 0 {
+        if let Some(hovering_way) = controller.hovering_way {
+            ev_input.send(InputEvent::ChangeWayWeight {
+                way: hovering_way,
+                delta: weight_delta,
+            });
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyU) {
+        if let Some(hovering_building) = controller.hovering_building {
+            ev_input.send(InputEvent::CycleUnitKind {
+                building: hovering_building,
+            });
+        }
+    }
+    if keys.just_pressed(KeyCode::Enter) {
+        ev_input.send(InputEvent::StartMatch);
+    }
+    if keys.just_pressed(KeyCode::KeyP) {
+        ev_input.send(InputEvent::TogglePause);
+    }
+    if keys.just_pressed(KeyCode::KeyR) {
+        ev_input.send(InputEvent::RestartMatch);
+    }
+    if keys.just_pressed(KeyCode::KeyB) {
+        ev_input.send(InputEvent::CycleBuildMode);
+    }
+    if keys.just_pressed(KeyCode::KeyF) {
+        ev_input.send(InputEvent::CycleReplaySpeed);
+    }
+    if keys.just_pressed(KeyCode::ArrowLeft) {
+        ev_input.send(InputEvent::SeekReplay {
+            seconds: -ReplayPlayback::SEEK_SECONDS,
+        });
+    }
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        ev_input.send(InputEvent::SeekReplay {
+            seconds: ReplayPlayback::SEEK_SECONDS,
+        });
+    }
+    if keys.just_pressed(KeyCode::Home) {
+        ev_input.send(InputEvent::SeekReplayStart);
+    }
+    if keys.just_pressed(KeyCode::KeyT) {
+        if let Some(hovering_way) = controller.hovering_way {
+            ev_input.send(InputEvent::ToggleWayDirection {
+                way: hovering_way,
+            });
+        }
+    }
 
     let (camera, camera_transform) = q_camera.single();
 
@@ -60,6 +138,7 @@ pub fn update(
         return;
     };
 
+    // the top of the terrain tiles
     let ground = Plane3d::default();
 
     // do a ray-plane intersection test, giving us the distance to the ground
@@ -92,6 +171,7 @@ pub fn update(
                 });
                 controller.hovering_building = Some(entity);
             }
+            controller.hovering_way = None;
             return;
         }
     }
@@ -102,6 +182,105 @@ pub fn update(
         });
         controller.hovering_building = None;
     }
+
+    controller.hovering_way = q_ways
+        .iter()
+        .find(|(_, way)| way.path.distance_xz(global_cursor.xz()) < Way::WIDTH / 2.0)
+        .map(|(entity, _)| entity);
+}
+
+/// Turns the input on hovered ways and buildings, and finished way and building placements, into
+/// commands of the local player, leaving it to the simulation whether they are allowed. Runs
+/// before the placements react to the same input.
+pub fn issue_commands(
+    mut ev_input: EventReader<InputEvent>,
+    mut ev_player_command: EventWriter<PlayerCommand>,
+    q_ways: Query<&Way>,
+    q_head_quarters: Query<&HeadQuarters>,
+    way_controller: Res<WayController>,
+    build_mode: Option<Res<BuildMode>>,
+    local_player: Res<LocalPlayer>,
+) {
+    let build_mode = build_mode.as_deref().filter(|build_mode| build_mode.is_active());
+    for event in ev_input.read() {
+        let command = match *event {
+            // the click on the first building starts placing, see `WayController::handle_input`
+            InputEvent::ClickedOnBuilding {
+                building,
+            } if build_mode.is_none() => {
+                let Some(from) = way_controller.start_building else {
+                    continue;
+                };
+                if from == building || !way_controller.placing_valid {
+                    continue;
+                }
+                GameCommand::ConnectBuildings {
+                    from,
+                    to: building,
+                    waypoints: way_controller.waypoints.clone(),
+                }
+            }
+            InputEvent::ClickedOnGround {
+                position,
+            } => {
+                let Some(BuildMode {
+                    selected: Some(selected),
+                    placing_valid: true,
+                    ..
+                }) = build_mode
+                else {
+                    continue;
+                };
+                GameCommand::PlaceBuilding {
+                    definition: selected.clone(),
+                    position: Terrain::cell_center(Terrain::cell(position)),
+                }
+            }
+            InputEvent::ToggleWayDirection {
+                way,
+            } => GameCommand::ToggleWayDirection {
+                way,
+            },
+            InputEvent::ChangeWayWeight {
+                way,
+                delta,
+            } => {
+                let Ok(current) = q_ways.get(way) else {
+                    continue;
+                };
+                let weight = current.weight.saturating_add_signed(delta).min(Way::MAX_WEIGHT);
+                if weight == current.weight {
+                    continue;
+                }
+                GameCommand::SetFlowWeight {
+                    way,
+                    weight,
+                }
+            }
+            // right clicking while placing a way only aborts the placement
+            InputEvent::RemoveWay {
+                way,
+            } if way_controller.start_building.is_none() => GameCommand::RemoveWay {
+                way,
+            },
+            InputEvent::CycleUnitKind {
+                building,
+            } => {
+                let Ok(head_quarters) = q_head_quarters.get(building) else {
+                    continue;
+                };
+                GameCommand::SelectUnitKind {
+                    building,
+                    kind: head_quarters.unit_kind.next(),
+                }
+            }
+            _ => continue,
+        };
+        ev_player_command.send(PlayerCommand {
+            player: local_player.0,
+            command,
+        });
+    }
 }
 
 #[derive(Event)]
@@ -115,11 +294,38 @@ pub enum InputEvent {
     ClickedOnBuilding {
         building: Entity,
     },
+    ClickedOnGround {
+        position: Vec3,
+    },
     EnterHoverBuilding {
         building: Entity,
     },
     ExitHoverBuilding {
         building: Entity,
     },
+    ToggleWayDirection {
+        way: Entity,
+    },
+    CycleUnitKind {
+        building: Entity,
+    },
+    /// Selects the next building to place, or leaves build mode after the last one.
+    CycleBuildMode,
+    RemoveWay {
+        way: Entity,
+    },
+    ChangeWayWeight {
+        way: Entity,
+        delta: i32,
+    },
     Abort,
+    StartMatch,
+    TogglePause,
+    RestartMatch,
+    CycleReplaySpeed,
+    /// Skips `seconds` of the replay, backward if negative.
+    SeekReplay {
+        seconds: f64,
+    },
+    SeekReplayStart,
 }
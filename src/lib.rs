@@ -0,0 +1,22 @@
+//! RTS game you play by controlling the flow of units between buildings
+
+pub mod ai;
+pub mod assets;
+pub mod building;
+pub mod combat;
+pub mod command;
+pub mod economy;
+pub mod game;
+pub mod headless;
+pub mod input;
+pub mod map;
+pub mod map_generator;
+pub mod placement;
+pub mod player;
+pub mod replay;
+pub mod rng;
+pub mod routing;
+pub mod terrain;
+pub mod unit;
+pub mod unit_kind;
+pub mod way;
@@ -1,7 +1,7 @@
 //! RTS game you play by controlling the flow of units between buildings
 
-use assets::AssetPlugin;
 use bevy::{
+    app::AppExit,
     diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
     gizmos::GizmoPlugin,
     log::LogPlugin,
@@ -13,17 +13,89 @@ use bevy::{
     },
 };
 use bevy_dev_console::prelude::*;
-use game::GamePlugin;
+use flow_rts::{
+    ai::Difficulty,
+    assets::AssetPlugin,
+    game::{GamePlugin, MatchState, SimulationPlugin, SimulationSettings},
+    headless::HeadlessPlugin,
+    map::MapSource,
+    map_generator::MapGenerator,
+    replay::{Replay, ReplayPlayback, ReplayRecorder},
+};
 
-mod assets;
-mod building;
-mod game;
-mod input;
-mod unit;
-mod way;
+const DEFAULT_MAP: &str = "maps/default.map.ron";
 
 fn main() {
+    let headless = std::env::args().any(|arg| arg == "--headless");
+
+    let mut settings = SimulationSettings::default();
+    if let Some(tick_rate) = arg_value("--tick-rate") {
+        settings.tick_rate = tick_rate.parse().expect("--tick-rate must be a number");
+    }
+    if let Some(seed) = arg_value("--seed") {
+        settings.seed = seed.parse().expect("--seed must be an unsigned integer");
+    }
+    settings.map = Some(if let Some(seed) = arg_value("--generate-map") {
+        MapSource::Generated(MapGenerator {
+            seed: seed.parse().expect("--generate-map must be an unsigned integer"),
+            ..default()
+        })
+    } else {
+        // path inside the assets folder
+        MapSource::File(arg_value("--map").unwrap_or_else(|| DEFAULT_MAP.to_owned()))
+    });
+
+    // players start windowed matches themselves
+    settings.lobby = !headless;
+
+    // e.g. `--ai 0:easy,1:hard`, otherwise the AI plays everyone but the local player in windows
+    settings.ai = match arg_value("--ai") {
+        Some(players) => players
+            .split(',')
+            .map(|player| {
+                let (index, difficulty) =
+                    player.split_once(':').expect("--ai expects index:difficulty pairs");
+                let index = index.parse().expect("--ai player index must be an unsigned integer");
+                (index, difficulty.parse().unwrap_or_else(|error| panic!("--ai: {error}")))
+            })
+            .collect(),
+        None if !headless => (1..4).map(|index| (index, Difficulty::default())).collect(),
+        None => default(),
+    };
+
     let mut app = App::new();
+    // replays bring their own settings and play back the commands of everyone
+    if let Some(path) = arg_value("--replay") {
+        let replay = Replay::load(path.as_ref())
+            .unwrap_or_else(|error| panic!("could not load replay {path:?}: {error}"));
+        settings = replay.settings();
+        app.insert_resource(ReplayPlayback::new(replay));
+    } else if let Some(path) = arg_value("--record") {
+        app.insert_resource(ReplayRecorder::new(path, &settings));
+    }
+    app.insert_resource(settings);
+    if headless {
+        app.add_plugins((HeadlessPlugin, LogPlugin::default(), SimulationPlugin))
+            .add_systems(OnEnter(MatchState::GameOver), exit_on_game_over);
+    } else {
+        add_windowed_plugins(&mut app);
+    }
+
+    app.run();
+}
+
+fn exit_on_game_over(mut ev_app_exit: EventWriter<AppExit>) {
+    ev_app_exit.send(AppExit);
+}
+
+/// Returns the argument following `name` on the command line.
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.position(|arg| arg == name)?;
+    args.next()
+}
+
+fn add_windowed_plugins(app: &mut App) {
     app.add_plugins((
         ConsoleLogPlugin::default(),
         DefaultPlugins
@@ -42,6 +114,11 @@ fn main() {
                 }),
                 ..default()
             })
+            .set(bevy::asset::AssetPlugin {
+                // hot-reloads edited building definitions and models
+                watch_for_changes_override: Some(true),
+                ..default()
+            })
             .disable::<LogPlugin>(),
         DevConsolePlugin,
         FrameTimeDiagnosticsPlugin,
@@ -52,8 +129,6 @@ fn main() {
     .insert_resource(Msaa::Sample4)
     .add_systems(Startup, setup)
     .add_systems(Update, debug);
-
-    app.run();
 }
 
 fn setup(mut commands: Commands) {
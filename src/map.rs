@@ -0,0 +1,217 @@
+use bevy::{app::AppExit, asset::LoadState, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::RonLoader,
+    building::SpawnBuilding,
+    economy::ResourceKind,
+    game::{SimulationSet, SimulationSettings},
+    map_generator::MapGenerator,
+    player::{Player, Players},
+    terrain::Terrain,
+};
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Map>()
+            .register_asset_loader(RonLoader::<Map>::new(&[Map::EXTENSION]))
+            .add_systems(Startup, CurrentMap::setup)
+            .add_systems(PreUpdate, CurrentMap::exit_on_failure)
+            .add_systems(
+                FixedUpdate,
+                CurrentMap::spawn.in_set(SimulationSet::Spawn).before(SpawnBuilding::handle),
+            );
+    }
+}
+
+/// Initial scenario of a match, loaded from a `*.map.ron` file.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone, PartialEq)]
+pub struct Map {
+    pub name: String,
+    /// Fills the cells of a rectangle that `tiles` leave empty.
+    #[serde(default)]
+    pub ground: Option<Ground>,
+    /// Tiles of the [`Terrain`] grid.
+    #[serde(default)]
+    pub tiles: Vec<MapTile>,
+    /// Props standing on top of the tiles, like rocks or bushes, e.g. `detail_rocks`.
+    #[serde(default)]
+    pub details: Vec<MapTile>,
+    /// Start buildings of every player.
+    pub players: Vec<PlayerStart>,
+    /// Buildings that belong to no player, like trees.
+    #[serde(default)]
+    pub neutral: Vec<MapBuilding>,
+    /// A player wins by fulfilling any of these.
+    #[serde(default)]
+    pub win_conditions: Vec<WinCondition>,
+}
+
+impl Map {
+    pub const EXTENSION: &'static str = "map.ron";
+
+    /// All buildings the map starts with, players' first.
+    pub fn buildings(&self) -> impl Iterator<Item = &MapBuilding> {
+        self.players.iter().flat_map(|player| &player.buildings).chain(&self.neutral)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Ground {
+    /// File name of the tile model, without the extension.
+    pub model: String,
+    /// Smallest column and row.
+    pub min: (i32, i32),
+    /// Largest column and row, inclusive.
+    pub max: (i32, i32),
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct MapTile {
+    /// File name of the tile model, without the extension, e.g. `tile_straight`.
+    pub model: String,
+    /// Column and row on the grid.
+    pub position: (i32, i32),
+    /// Quarter turns counterclockwise.
+    #[serde(default)]
+    pub rotation: u8,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PlayerStart {
+    /// Players with the same team are allies. Defaults to the index of the player, so players
+    /// without a team play on their own.
+    #[serde(default)]
+    pub team: Option<u32>,
+    pub buildings: Vec<MapBuilding>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct MapBuilding {
+    /// Name of the [`BuildingDefinition`](crate::building::definition::BuildingDefinition).
+    pub definition: String,
+    /// X and Z coordinates on the ground.
+    pub position: (f32, f32),
+}
+
+impl MapBuilding {
+    pub fn translation(&self) -> Vec3 {
+        Vec3::new(self.position.0, 0.0, self.position.1)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum WinCondition {
+    /// Destroy the head quarters of all other players.
+    DestroyHeadQuarters,
+    /// Hold `count` buildings at once for `seconds`.
+    HoldBuildings {
+        count: u32,
+        seconds: f32,
+    },
+    /// Stockpile `amount` of a resource.
+    Resources {
+        kind: ResourceKind,
+        amount: u32,
+    },
+}
+
+/// Where the map of a match comes from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MapSource {
+    /// Asset path of a `*.map.ron` file.
+    File(String),
+    Generated(MapGenerator),
+}
+
+/// The map of [`SimulationSettings::map`], if any.
+#[derive(Resource)]
+pub struct CurrentMap {
+    pub handle: Option<Handle<Map>>,
+    spawned: bool,
+}
+
+impl CurrentMap {
+    pub fn setup(
+        mut commands: Commands,
+        settings: Res<SimulationSettings>,
+        asset_server: Res<AssetServer>,
+        mut maps: ResMut<Assets<Map>>,
+    ) {
+        let handle = settings.map.as_ref().map(|source| match source {
+            MapSource::File(path) => asset_server.load(path.clone()),
+            MapSource::Generated(generator) => maps.add(generator.generate()),
+        });
+        commands.insert_resource(CurrentMap {
+            handle,
+            spawned: false,
+        });
+    }
+
+    /// Run condition for the simulation, which must not start before the map is there.
+    pub fn loaded(current_map: Option<Res<CurrentMap>>, maps: Res<Assets<Map>>) -> bool {
+        current_map.is_some_and(|current_map| match &current_map.handle {
+            Some(handle) => maps.contains(handle),
+            None => true,
+        })
+    }
+
+    pub fn exit_on_failure(
+        current_map: Res<CurrentMap>,
+        asset_server: Res<AssetServer>,
+        mut ev_app_exit: EventWriter<AppExit>,
+    ) {
+        let Some(handle) = &current_map.handle else {
+            return;
+        };
+        // generated maps are not loaded and have no load state
+        if asset_server.load_state(handle) == LoadState::Failed {
+            error!("could not load map {:?}", handle.path());
+            ev_app_exit.send(AppExit);
+        }
+    }
+
+    /// Spawns the map again in the next tick, after a restart despawned everything.
+    pub fn respawn(&mut self) {
+        self.spawned = false;
+    }
+
+    /// Lays out the map's terrain and spawns its players and buildings in the first tick.
+    pub fn spawn(
+        mut commands: Commands,
+        mut current_map: ResMut<CurrentMap>,
+        maps: Res<Assets<Map>>,
+        mut terrain: ResMut<Terrain>,
+        mut players: ResMut<Players>,
+        mut ev_spawn_building: EventWriter<SpawnBuilding>,
+    ) {
+        if current_map.spawned {
+            return;
+        }
+        current_map.spawned = true;
+        let Some(map) = current_map.handle.as_ref().and_then(|handle| maps.get(handle)) else {
+            return;
+        };
+        info!("starting map {:?}", map.name);
+        *terrain = Terrain::from_map(map);
+        players.0 = Player::spawn_all(&mut commands, map);
+        for (start, player) in map.players.iter().zip(&players.0) {
+            for building in &start.buildings {
+                ev_spawn_building.send(SpawnBuilding {
+                    definition: building.definition.clone(),
+                    position: building.translation(),
+                    owner: Some(*player),
+                });
+            }
+        }
+        for building in &map.neutral {
+            ev_spawn_building.send(SpawnBuilding {
+                definition: building.definition.clone(),
+                position: building.translation(),
+                owner: None,
+            });
+        }
+    }
+}
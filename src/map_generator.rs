@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    building::definition::BuildingDefinitions,
+    map::{Ground, Map, MapBuilding, MapTile, PlayerStart, WinCondition},
+    rng::SimulationRng,
+    terrain::Terrain,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Biome {
+    #[default]
+    Grass,
+    Snow,
+}
+
+impl Biome {
+    /// Name of the tile kit model for this biome.
+    fn tile(self, name: &str) -> String {
+        match self {
+            Biome::Grass => name.to_owned(),
+            Biome::Snow => format!("snow_{name}"),
+        }
+    }
+}
+
+/// Generates maps that are rotationally symmetric around the center, so every player starts
+/// with the same terrain and resources around them. The same parameters always give the same map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapGenerator {
+    pub seed: u64,
+    /// Width and depth in tiles, rounded up to an odd number so a tile sits in the center.
+    pub size: u32,
+    /// Between 1 and 4, starting in the corners.
+    pub player_count: u32,
+    /// Chance of every free tile to hold a tree.
+    pub resource_density: f32,
+    /// Rivers flowing in from the edge of the map, before copying them for the symmetry.
+    pub river_count: u32,
+    pub biome: Biome,
+}
+
+impl Default for MapGenerator {
+    fn default() -> Self {
+        MapGenerator {
+            seed: 0,
+            size: 32,
+            player_count: 2,
+            resource_density: 0.04,
+            river_count: 1,
+            biome: Biome::Grass,
+        }
+    }
+}
+
+impl MapGenerator {
+    pub const MIN_SIZE: u32 = 16;
+    /// Tiles between the corner of the map and a player's head quarters.
+    const START_INSET: i32 = 3;
+    /// Tiles around the head quarters that are kept free.
+    const START_CLEARANCE: i32 = 2;
+    /// Chance of every tile left free to hold a detail prop.
+    const DETAIL_DENSITY: f32 = 0.1;
+    const DETAILS: [&'static str; 8] = [
+        "detail_crystal",
+        "detail_crystalLarge",
+        "detail_dirt",
+        "detail_dirtLarge",
+        "detail_rocks",
+        "detail_rocksLarge",
+        "detail_tree",
+        "detail_treeLarge",
+    ];
+
+    pub fn generate(&self) -> Map {
+        assert!((1..=4).contains(&self.player_count), "maps are made for 1 to 4 players");
+        assert!(self.size >= Self::MIN_SIZE, "maps must be at least {} tiles", Self::MIN_SIZE);
+
+        let mut rng = SimulationRng::new(self.seed);
+        let half = (self.size / 2) as i32;
+        // quarter turns mapping the map onto itself
+        let symmetry = if self.player_count <= 2 { 2 } else { 4 };
+        let step = 4 / symmetry;
+        let orbit = |cell: (i32, i32)| {
+            (0..symmetry).map(move |i| (rotate(cell, i * step), (i * step) as u8))
+        };
+
+        let start = (-half + Self::START_INSET, -half + Self::START_INSET);
+        let starts: Vec<_> = orbit(start).map(|(cell, _)| cell).collect();
+        let near_start = |cell: (i32, i32)| {
+            starts.iter().any(|start| {
+                (cell.0 - start.0).abs() <= Self::START_CLEARANCE
+                    && (cell.1 - start.1).abs() <= Self::START_CLEARANCE
+            })
+        };
+
+        let mut tiles = BTreeMap::new();
+        // rivers run along rows from the east edge towards the center without reaching it, so
+        // neither they nor their rotated copies cross each other. On small maps they may reach
+        // into the start areas, which stay clear.
+        let mut river_rows = BTreeSet::new();
+        for _ in 0..self.river_count {
+            let row = 1 + rng.below((half / 2 - 1) as u32) as i32;
+            if !river_rows.insert(row) {
+                continue;
+            }
+            let end = 1 + rng.below((half - 1) as u32) as i32;
+            let bridge = end + rng.below((half - end + 1) as u32) as i32;
+            for x in (end..=half).filter(|x| !near_start((*x, row))) {
+                let model = if x == bridge { "tile_riverBridge" } else { "tile_riverStraight" };
+                for (cell, rotation) in orbit((x, row)) {
+                    tiles.insert(cell, (model, 1 + rotation));
+                }
+            }
+        }
+
+        let mut neutral = Vec::new();
+        let mut free = Vec::new();
+        for x in -half..=half {
+            for z in -half..=half {
+                let cell = (x, z);
+                // decide once per orbit and copy the decision to the other cells, the center is
+                // its own orbit and stays free
+                if cell == (0, 0)
+                    || orbit(cell).any(|(other, _)| other < cell)
+                    || tiles.contains_key(&cell)
+                    || near_start(cell)
+                {
+                    continue;
+                }
+                let roll = rng.next_f32();
+                if roll >= self.resource_density * 1.5 {
+                    free.push(cell);
+                } else if roll < self.resource_density {
+                    for (cell, _) in orbit(cell) {
+                        neutral.push(MapBuilding {
+                            definition: "tree".to_owned(),
+                            position: cell_position(cell),
+                        });
+                    }
+                } else {
+                    let obstacles = ["tile_rock", "tile_crystal", "tile_treeDouble", "tile_hill"];
+                    let model = obstacles[rng.below(obstacles.len() as u32) as usize];
+                    let rotation = rng.below(4) as u8;
+                    for (cell, turns) in orbit(cell) {
+                        tiles.insert(cell, (model, rotation + turns));
+                    }
+                }
+            }
+        }
+
+        // details come from their own sequence, so they don't change the layout above
+        let mut detail_rng = SimulationRng::new(rng.next_u64());
+        let mut details = BTreeMap::new();
+        for cell in free {
+            if detail_rng.next_f32() >= Self::DETAIL_DENSITY {
+                continue;
+            }
+            let model = Self::DETAILS[detail_rng.below(Self::DETAILS.len() as u32) as usize];
+            let rotation = detail_rng.below(4) as u8;
+            for (cell, turns) in orbit(cell) {
+                details.insert(cell, (model, rotation + turns));
+            }
+        }
+
+        let map_tiles = |tiles: BTreeMap<(i32, i32), (&str, u8)>| {
+            tiles
+                .into_iter()
+                .map(|(position, (model, rotation))| MapTile {
+                    model: self.biome.tile(model),
+                    position,
+                    rotation: rotation % 4,
+                })
+                .collect()
+        };
+        Map {
+            name: format!("Generated {}", self.seed),
+            ground: Some(Ground {
+                model: self.biome.tile("tile"),
+                min: (-half, -half),
+                max: (half, half),
+            }),
+            tiles: map_tiles(tiles),
+            details: map_tiles(details),
+            players: starts
+                .iter()
+                .take(self.player_count as usize)
+                .map(|start| PlayerStart {
+                    team: None,
+                    buildings: vec![MapBuilding {
+                        definition: BuildingDefinitions::HEAD_QUARTERS.to_owned(),
+                        position: cell_position(*start),
+                    }],
+                })
+                .collect(),
+            neutral,
+            win_conditions: vec![WinCondition::DestroyHeadQuarters],
+        }
+    }
+}
+
+/// Rotates a grid cell around the center of the map, in the direction tiles are rotated.
+fn rotate(cell: (i32, i32), quarter_turns: u32) -> (i32, i32) {
+    (0..quarter_turns).fold(cell, |(x, z), _| (z, -x))
+}
+
+fn cell_position(cell: (i32, i32)) -> (f32, f32) {
+    let center = Terrain::cell_center(cell);
+    (center.x, center.z)
+}
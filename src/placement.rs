@@ -0,0 +1,50 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    building::{definition::BuildingDefinition, Building},
+    terrain::Terrain,
+    way::{Way, WayPath},
+};
+
+/// Decides where buildings may stand and ways may run, the same for the simulation accepting
+/// commands and for the previews showing players whether their command would be accepted.
+#[derive(SystemParam)]
+pub struct Placement<'w, 's> {
+    terrain: Res<'w, Terrain>,
+    definitions: Res<'w, Assets<BuildingDefinition>>,
+    q_buildings: Query<
+        'w,
+        's,
+        (Entity, &'static Handle<BuildingDefinition>, &'static Transform),
+        With<Building>,
+    >,
+    q_ways: Query<'w, 's, &'static Way>,
+}
+
+impl Placement<'_, '_> {
+    /// Whether a building of `definition` fits at `position`, on a buildable tile without
+    /// overlapping any other building or way.
+    pub fn is_building_clear(&self, definition: &BuildingDefinition, position: Vec3) -> bool {
+        let footprint = definition.footprint(position);
+        self.terrain.is_buildable(position)
+            && self.footprints().all(|(_, other)| other.intersect(footprint).is_empty())
+            && self.q_ways.iter().all(|way| !way.path.overlaps(footprint, Way::WIDTH))
+    }
+
+    /// Whether a way along `path` only crosses walkable tiles and touches no building but `ends`,
+    /// the buildings it connects.
+    pub fn is_way_clear(&self, path: &WayPath, ends: &[Entity]) -> bool {
+        self.terrain.is_walkable_along(path)
+            && self
+                .footprints()
+                .filter(|(entity, _)| !ends.contains(entity))
+                .all(|(_, footprint)| !path.overlaps(footprint, Way::WIDTH))
+    }
+
+    /// Ground covered by each building.
+    fn footprints(&self) -> impl Iterator<Item = (Entity, Rect)> + '_ {
+        self.q_buildings.iter().filter_map(|(entity, handle, transform)| {
+            Some((entity, self.definitions.get(handle)?.footprint(transform.translation)))
+        })
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::map::Map;
+
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Players>().init_resource::<LocalPlayer>();
+    }
+}
+
+/// Someone controlling buildings, units and ways, spawned for every
+/// [`PlayerStart`](crate::map::PlayerStart) of the map.
+#[derive(Component, Debug, Clone)]
+pub struct Player {
+    /// Position in [`Players`].
+    pub index: usize,
+    pub name: String,
+    /// Tints everything the player owns.
+    pub color: Color,
+    /// The [`Team`] entity.
+    pub team: Entity,
+}
+
+impl Player {
+    pub const COLORS: [Color; 4] = [
+        Color::rgb(0.2, 0.4, 0.9),
+        Color::rgb(0.9, 0.2, 0.2),
+        Color::rgb(0.95, 0.8, 0.2),
+        Color::rgb(0.3, 0.8, 0.3),
+    ];
+
+    /// The player at `index` in [`Players`], named and colored by it.
+    pub fn new(index: usize, team: Entity) -> Self {
+        Player {
+            index,
+            name: format!("Player {}", index + 1),
+            color: Self::COLORS[index % Self::COLORS.len()],
+            team,
+        }
+    }
+
+    /// Spawns the players of the map with their teams, in the order of the map's player starts.
+    pub fn spawn_all(commands: &mut Commands, map: &Map) -> Vec<Entity> {
+        let mut teams = BTreeMap::new();
+        map.players
+            .iter()
+            .enumerate()
+            .map(|(index, start)| {
+                let number = start.team.unwrap_or(index as u32);
+                let team = *teams.entry(number).or_insert_with(|| {
+                    commands
+                        .spawn(Team {
+                            number,
+                        })
+                        .id()
+                });
+                commands.spawn(Player::new(index, team)).id()
+            })
+            .collect()
+    }
+}
+
+/// Players that win or lose together and whose units don't fight each other.
+#[derive(Component, Debug, Clone)]
+pub struct Team {
+    /// [`PlayerStart::team`](crate::map::PlayerStart::team) of its players.
+    pub number: u32,
+}
+
+/// The [`Player`] entity a building, way or unit belongs to. Without one, like trees, it is
+/// neutral.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner(pub Entity);
+
+impl Owner {
+    /// Whether the owners are players of different teams.
+    pub fn is_enemy_of(&self, other: &Owner, q_players: &Query<&Player>) -> bool {
+        match (q_players.get(self.0), q_players.get(other.0)) {
+            (Ok(player), Ok(other)) => player.team != other.team,
+            _ => false,
+        }
+    }
+}
+
+/// All player entities, so a player can be referred to by its index.
+#[derive(Resource, Debug, Default)]
+pub struct Players(pub Vec<Entity>);
+
+impl Players {
+    pub fn get(&self, index: usize) -> Option<Entity> {
+        self.0.get(index).copied()
+    }
+
+    /// Whether the player at `index` may control something with this owner. In a world without
+    /// players, like an empty one, everything can be controlled.
+    pub fn controls(&self, index: usize, owner: Option<&Owner>) -> bool {
+        if self.0.is_empty() {
+            return true;
+        }
+        owner.is_some_and(|owner| Some(owner.0) == self.get(index))
+    }
+}
+
+/// Index of the player whose buildings the input controls.
+#[derive(Resource, Debug, Default)]
+pub struct LocalPlayer(pub usize);
+
+impl LocalPlayer {
+    pub fn entity(&self, players: &Players) -> Option<Entity> {
+        players.get(self.0)
+    }
+
+    pub fn controls(&self, players: &Players, owner: Option<&Owner>) -> bool {
+        players.controls(self.0, owner)
+    }
+}
@@ -0,0 +1,452 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    app::{AppExit, FixedMain},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::Ai,
+    building::Building,
+    command::{GameCommand, PlayerCommand},
+    game::{Game, MatchState, RestartMatch, SimulationSet, SimulationSettings, SimulationTick},
+    input::InputEvent,
+    map::MapSource,
+    unit::Unit,
+    way::Way,
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NextSimulationId>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    SimulationId::assign
+                        .in_set(SimulationSet::Interact)
+                        .before(Ai::update)
+                        .before(ReplayPlayback::send_commands)
+                        .before(PlayerCommand::handle),
+                    // again for the ways and buildings the commands placed, so everything the
+                    // simulation updates has an id
+                    SimulationId::assign
+                        .after(SimulationSet::Interact)
+                        .before(SimulationSet::Update)
+                        .run_if(in_state(MatchState::Playing)),
+                    ReplayPlayback::send_commands
+                        .in_set(SimulationSet::Interact)
+                        .before(PlayerCommand::handle)
+                        .run_if(resource_exists::<ReplayPlayback>),
+                    ReplayRecorder::record
+                        .in_set(SimulationSet::Interact)
+                        .after(PlayerCommand::handle)
+                        .run_if(resource_exists::<ReplayRecorder>),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    ReplayRecorder::restart.run_if(resource_exists::<ReplayRecorder>),
+                    ReplayPlayback::seek
+                        .before(Game::restart)
+                        .run_if(resource_exists::<ReplayPlayback>),
+                ),
+            )
+            .add_systems(
+                OnEnter(MatchState::GameOver),
+                ReplayRecorder::save.run_if(resource_exists::<ReplayRecorder>),
+            )
+            .add_systems(
+                Last,
+                ReplayRecorder::save
+                    .run_if(resource_exists::<ReplayRecorder>.and_then(on_event::<AppExit>())),
+            );
+    }
+}
+
+/// Keys controlling a [`ReplayPlayback`] and the text showing its progress.
+pub struct ReplayControlsPlugin;
+
+impl Plugin for ReplayControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, ReplayScreen::setup).add_systems(
+            Update,
+            (ReplayPlayback::handle_input, ReplayScreen::update)
+                .run_if(resource_exists::<ReplayPlayback>),
+        );
+    }
+}
+
+/// Identifies a building, way or unit the same way in every run of a match, unlike its
+/// [`Entity`], which depends on everything else spawned in the app, like the visuals. The
+/// simulation breaks ties on these ids wherever it needs a fixed order.
+#[derive(
+    Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct SimulationId(pub u32);
+
+/// The [`SimulationId`] given out next, reset with the match.
+#[derive(Resource, Debug, Default)]
+pub struct NextSimulationId(pub u32);
+
+impl SimulationId {
+    /// Numbers new buildings by their position, new ways by the buildings they connect and new
+    /// units by the lane they entered, where only one unit can enter per tick. So the ids don't
+    /// depend on the order the entities were spawned in.
+    #[allow(clippy::type_complexity)]
+    pub fn assign(
+        mut commands: Commands,
+        mut next_id: ResMut<NextSimulationId>,
+        q_new_buildings: Query<(Entity, &Transform), (With<Building>, Without<SimulationId>)>,
+        q_new_ways: Query<(Entity, &Way), Without<SimulationId>>,
+        q_new_units: Query<(Entity, &Unit), Without<SimulationId>>,
+        q_ids: Query<&SimulationId>,
+    ) {
+        let mut give_id = |entity: Entity| {
+            let id = SimulationId(next_id.0);
+            next_id.0 += 1;
+            commands.entity(entity).insert(id);
+            id
+        };
+
+        let mut buildings: Vec<_> = q_new_buildings.iter().collect();
+        buildings.sort_by(|(_, a), (_, b)| {
+            let (a, b) = (a.translation, b.translation);
+            a.x.total_cmp(&b.x).then(a.z.total_cmp(&b.z))
+        });
+        let mut new_ids = BTreeMap::new();
+        for (entity, _) in buildings {
+            new_ids.insert(entity, give_id(entity));
+        }
+
+        let id_of = |entity: Entity| q_ids.get(entity).ok().or(new_ids.get(&entity)).copied();
+        let mut ways: Vec<_> = q_new_ways
+            .iter()
+            .map(|(entity, way)| (entity, (id_of(way.from), id_of(way.to))))
+            .collect();
+        ways.sort_by_key(|(_, ends)| *ends);
+        for (entity, _) in ways {
+            new_ids.insert(entity, give_id(entity));
+        }
+
+        let id_of = |entity: Entity| q_ids.get(entity).ok().or(new_ids.get(&entity)).copied();
+        let mut units: Vec<_> = q_new_units
+            .iter()
+            .map(|(entity, unit)| (entity, (id_of(unit.way), unit.reversed)))
+            .collect();
+        units.sort_by_key(|(_, lane)| *lane);
+        for (entity, _) in units {
+            give_id(entity);
+        }
+    }
+}
+
+/// Everything needed to play a match again: the settings it started with and the commands of
+/// all players, humans and AIs alike, with the tick they were issued in.
+///
+/// Replaying only reproduces the match as long as the simulation is deterministic, which it is
+/// for the same commands, windowed or headless, because it orders by [`SimulationId`]s instead of
+/// entities.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub tick_rate: f64,
+    pub seed: u64,
+    pub map: Option<MapSource>,
+    /// Sorted by tick.
+    pub commands: Vec<RecordedCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    pub tick: u64,
+    /// Index in [`Players`](crate::player::Players).
+    pub player: usize,
+    pub command: GameCommand<SimulationId>,
+}
+
+impl Replay {
+    pub fn new(settings: &SimulationSettings) -> Self {
+        Replay {
+            tick_rate: settings.tick_rate,
+            seed: settings.seed,
+            map: settings.map.clone(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Settings starting the recorded match right away, without any AI, since its commands are
+    /// part of the replay.
+    pub fn settings(&self) -> SimulationSettings {
+        SimulationSettings {
+            tick_rate: self.tick_rate,
+            seed: self.seed,
+            map: self.map.clone(),
+            ..default()
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(fs::write(path, ron::to_string(self)?)?)
+    }
+}
+
+/// Records the commands of the running match into a [`Replay`], which is saved to `path` when
+/// the match is over or the app exits.
+#[derive(Resource, Debug)]
+pub struct ReplayRecorder {
+    pub path: PathBuf,
+    pub replay: Replay,
+}
+
+impl ReplayRecorder {
+    pub fn new(path: impl Into<PathBuf>, settings: &SimulationSettings) -> Self {
+        ReplayRecorder {
+            path: path.into(),
+            replay: Replay::new(settings),
+        }
+    }
+
+    pub fn record(
+        mut recorder: ResMut<ReplayRecorder>,
+        mut ev_player_command: EventReader<PlayerCommand>,
+        tick: Res<SimulationTick>,
+        q_ids: Query<&SimulationId>,
+    ) {
+        for event in ev_player_command.read() {
+            // commands about anything else than buildings and ways are rejected in every run
+            let Some(command) = event.command.clone().map(|entity| q_ids.get(entity).ok().copied())
+            else {
+                continue;
+            };
+            recorder.replay.commands.push(RecordedCommand {
+                tick: tick.0,
+                player: event.player,
+                command,
+            });
+        }
+    }
+
+    /// Starts over with the match.
+    pub fn restart(
+        mut recorder: ResMut<ReplayRecorder>,
+        mut ev_restart_match: EventReader<RestartMatch>,
+    ) {
+        if ev_restart_match.read().count() > 0 {
+            recorder.replay.commands.clear();
+        }
+    }
+
+    pub fn save(recorder: Res<ReplayRecorder>) {
+        match recorder.replay.save(&recorder.path) {
+            Ok(()) => info!("saved replay to {:?}", recorder.path),
+            Err(error) => error!("could not save replay to {:?}: {}", recorder.path, error),
+        }
+    }
+}
+
+/// Plays a [`Replay`] back in place of the input and AIs.
+#[derive(Resource, Debug)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    /// Tick to jump to, by simulating the match up to it as fast as possible.
+    pub seek: Option<u64>,
+    /// Whether seeking started while paused, so the match pauses again once it is done. Seeking
+    /// backward restarts the match, which starts playing it.
+    pause_after_seek: bool,
+}
+
+impl ReplayPlayback {
+    /// Speeds to cycle through, relative to real time.
+    pub const SPEEDS: [f32; 4] = [1.0, 2.0, 4.0, 8.0];
+    /// Seconds of the match to skip backward or forward at once.
+    pub const SEEK_SECONDS: f64 = 10.0;
+    /// Ticks simulated per frame while seeking, so the app keeps responding.
+    const SEEK_TICKS_PER_FRAME: u32 = 600;
+
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayback {
+            replay,
+            seek: None,
+            pause_after_seek: false,
+        }
+    }
+
+    /// Issues the commands recorded for the current tick, and nothing else.
+    pub fn send_commands(
+        playback: Res<ReplayPlayback>,
+        mut ev_player_command: ResMut<Events<PlayerCommand>>,
+        tick: Res<SimulationTick>,
+        q_ids: Query<(Entity, &SimulationId)>,
+    ) {
+        ev_player_command.clear();
+        let commands = &playback.replay.commands;
+        let start = commands.partition_point(|recorded| recorded.tick < tick.0);
+        let recorded: Vec<_> =
+            commands[start..].iter().take_while(|recorded| recorded.tick == tick.0).collect();
+        if recorded.is_empty() {
+            return;
+        }
+        let entities: BTreeMap<_, _> = q_ids.iter().map(|(entity, id)| (*id, entity)).collect();
+        for recorded in recorded {
+            if let Some(command) = recorded.command.clone().map(|id| entities.get(&id).copied()) {
+                ev_player_command.send(PlayerCommand {
+                    player: recorded.player,
+                    command,
+                });
+            }
+        }
+    }
+
+    /// Simulates the match up to [`ReplayPlayback::seek`], restarting it first to get back to
+    /// earlier ticks. Works while paused as well, which the match stays afterwards. Stops early if
+    /// the match ends.
+    pub fn seek(world: &mut World) {
+        let Some(target) = world.resource::<ReplayPlayback>().seek else {
+            return;
+        };
+        let state = *world.resource::<State<MatchState>>().get();
+        if state == MatchState::Paused {
+            world.resource_mut::<ReplayPlayback>().pause_after_seek = true;
+        }
+        if world.resource::<SimulationTick>().0 > target {
+            world.send_event(RestartMatch);
+            return;
+        }
+        match state {
+            MatchState::Playing | MatchState::Paused => {}
+            // nothing is left to simulate
+            MatchState::GameOver => {
+                Self::finish_seeking(world);
+                return;
+            }
+            MatchState::Loading | MatchState::Lobby => return,
+        }
+
+        // the simulation only runs while playing, setting the state directly skips the systems
+        // of an actual transition, like the ones showing the pause screen
+        world.insert_resource(State::new(MatchState::Playing));
+        // runs ticks the same way `FixedMain` runs when enough time accumulated
+        let timestep = world.resource::<Time<Fixed>>().timestep();
+        let mut ticks = 0;
+        while world.resource::<SimulationTick>().0 < target
+            && !world.resource::<Game>().is_over()
+            && ticks < Self::SEEK_TICKS_PER_FRAME
+        {
+            world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+            *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+            world.run_schedule(FixedMain);
+            ticks += 1;
+        }
+        *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+        world.insert_resource(State::new(state));
+
+        if world.resource::<SimulationTick>().0 >= target || world.resource::<Game>().is_over() {
+            Self::finish_seeking(world);
+        }
+    }
+
+    fn finish_seeking(world: &mut World) {
+        let mut playback = world.resource_mut::<ReplayPlayback>();
+        playback.seek = None;
+        let pause = std::mem::take(&mut playback.pause_after_seek);
+        // unless the match ended while seeking
+        let mut next_state = world.resource_mut::<NextState<MatchState>>();
+        if pause && next_state.0.is_none() {
+            next_state.set(MatchState::Paused);
+        }
+    }
+
+    /// Changes the speed and seeks on the player's request. Pausing works like in every match.
+    pub fn handle_input(
+        mut ev_input: EventReader<InputEvent>,
+        mut playback: ResMut<ReplayPlayback>,
+        mut time: ResMut<Time<Virtual>>,
+        tick: Res<SimulationTick>,
+    ) {
+        for event in ev_input.read() {
+            match *event {
+                InputEvent::CycleReplaySpeed => {
+                    let current = Self::SPEEDS
+                        .iter()
+                        .position(|speed| *speed == time.relative_speed())
+                        .unwrap_or(0);
+                    time.set_relative_speed(Self::SPEEDS[(current + 1) % Self::SPEEDS.len()]);
+                }
+                InputEvent::SeekReplay {
+                    seconds,
+                } => {
+                    let from = playback.seek.unwrap_or(tick.0);
+                    let ticks = (seconds * playback.replay.tick_rate).round() as i64;
+                    playback.seek = Some(from.saturating_add_signed(ticks));
+                }
+                InputEvent::SeekReplayStart => {
+                    playback.seek = Some(0);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Text in the corner of the screen showing the progress and speed of the [`ReplayPlayback`].
+#[derive(Component)]
+pub struct ReplayScreen;
+
+impl ReplayScreen {
+    pub fn setup(mut commands: Commands) {
+        commands.spawn((
+            ReplayScreen,
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            }),
+        ));
+    }
+
+    pub fn update(
+        playback: Res<ReplayPlayback>,
+        tick: Res<SimulationTick>,
+        time: Res<Time<Virtual>>,
+        mut q_screen: Query<&mut Text, With<ReplayScreen>>,
+    ) {
+        let Ok(mut text) = q_screen.get_single_mut() else {
+            return;
+        };
+        let clock = |tick: u64| {
+            let seconds = (tick as f64 / playback.replay.tick_rate) as u64;
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        };
+        let last_command = playback.replay.commands.last().map_or(0, |recorded| recorded.tick);
+        let progress = match playback.seek {
+            Some(target) => format!("seeking to {}", clock(target)),
+            None => format!("{}x", time.relative_speed()),
+        };
+        text.sections[0].value = format!(
+            "Replay {} / {}, {}\nF: speed, Left/Right: seek, Home: start, P: pause",
+            clock(tick.0),
+            clock(last_command),
+            progress,
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::{replay::SimulationId, way::Way};
+
+/// Directed graph of the buildings and the ways units may currently travel along.
+#[derive(Debug, Default, Clone)]
+pub struct WayGraph {
+    /// Reachable neighbours of each building with the way leading there and its length, in the
+    /// order of the ways' ids.
+    edges: BTreeMap<Entity, Vec<Edge>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    to: Entity,
+    way: Entity,
+    length: f32,
+}
+
+impl WayGraph {
+    pub fn new<'a>(ways: impl IntoIterator<Item = (Entity, &'a Way, &'a SimulationId)>) -> Self {
+        let mut ways: Vec<_> = ways.into_iter().collect();
+        ways.sort_by_key(|(.., id)| **id);
+        let mut edges: BTreeMap<Entity, Vec<Edge>> = BTreeMap::new();
+        for (entity, way, _) in ways {
+            for building in [way.from, way.to] {
+                if let Some(destination) = way.destination_from(building) {
+                    edges.entry(building).or_default().push(Edge {
+                        to: destination,
+                        way: entity,
+                        length: way.path.length(),
+                    });
+                }
+            }
+        }
+        WayGraph {
+            edges,
+        }
+    }
+
+    /// The way units may take to travel directly from `from` to `to`.
+    pub fn way_between(&self, from: Entity, to: Entity) -> Option<Entity> {
+        self.edges.get(&from)?.iter().find(|edge| edge.to == to).map(|edge| edge.way)
+    }
+
+    /// Buildings to pass on the shortest way from `from` to `to`, excluding `from` and including
+    /// `to`. Empty if both are the same building, `None` if `to` can't be reached.
+    ///
+    /// Ties are broken by the order the buildings were reached in, following the ways in the
+    /// order of their ids, so the result only depends on the simulation.
+    pub fn shortest_path(&self, from: Entity, to: Entity) -> Option<Vec<Entity>> {
+        // building, distance and previous building of every building reached so far, in the
+        // order they were reached
+        let mut reached: Vec<(Entity, f32, Option<Entity>)> = vec![(from, 0.0, None)];
+        let mut done: Vec<Entity> = Vec::new();
+        let find = |reached: &[(Entity, f32, Option<Entity>)], building: Entity| {
+            reached.iter().position(|(other, ..)| *other == building)
+        };
+
+        loop {
+            let (current, distance, _) = reached
+                .iter()
+                .filter(|(building, ..)| !done.contains(building))
+                .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+                .copied()?;
+            if current == to {
+                break;
+            }
+            done.push(current);
+            for edge in self.edges.get(&current).into_iter().flatten() {
+                let candidate = distance + edge.length;
+                match find(&reached, edge.to) {
+                    Some(index) if reached[index].1 <= candidate => {}
+                    Some(index) => reached[index] = (edge.to, candidate, Some(current)),
+                    None => reached.push((edge.to, candidate, Some(current))),
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut current = to;
+        while let Some(&(_, _, Some(previous))) =
+            find(&reached, current).map(|index| &reached[index])
+        {
+            path.push(current);
+            current = previous;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::{
+    map::{Map, MapTile},
+    way::WayPath,
+};
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Terrain>();
+    }
+}
+
+pub struct TerrainVisualsPlugin;
+
+impl Plugin for TerrainVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, Terrain::insert_scenes);
+    }
+}
+
+/// What may be placed on a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileProperties {
+    /// Ways may cross the tile.
+    pub walkable: bool,
+    /// Buildings may stand on the tile.
+    pub buildable: bool,
+}
+
+impl TileProperties {
+    const OPEN: Self = TileProperties {
+        walkable: true,
+        buildable: true,
+    };
+    const BLOCKED: Self = TileProperties {
+        walkable: false,
+        buildable: false,
+    };
+    const STEEP: Self = TileProperties {
+        walkable: true,
+        buildable: false,
+    };
+
+    /// Properties of a tile kit model by its name, snow tiles behave like their grass
+    /// counterparts.
+    pub fn of(model: &str) -> Self {
+        let name = model.strip_prefix("snow_").unwrap_or(model);
+        if name.starts_with("tile_riverBridge") || name.starts_with("detail_") {
+            Self::STEEP
+        } else if name.starts_with("tile_river")
+            || name.starts_with("tile_rock")
+            || name.starts_with("tile_tree")
+            || name.starts_with("tile_crystal")
+        {
+            Self::BLOCKED
+        } else if ["Hill", "hill", "slope", "High"].iter().any(|part| name.contains(part)) {
+            Self::STEEP
+        } else {
+            Self::OPEN
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tile {
+    /// File name of the model, without the extension.
+    pub model: String,
+    /// Quarter turns counterclockwise.
+    pub rotation: u8,
+    pub properties: TileProperties,
+}
+
+/// Ground tiles on a grid of [`Terrain::TILE_SIZE`], with cell `(0, 0)` centered on the origin.
+///
+/// Without any tiles, like in an empty world, everything is walkable and buildable. Otherwise,
+/// cells without a tile are neither. Details on top of a tile may restrict it further.
+#[derive(Resource, Debug, Default)]
+pub struct Terrain {
+    tiles: BTreeMap<(i32, i32), Tile>,
+    details: BTreeMap<(i32, i32), Tile>,
+}
+
+impl Terrain {
+    pub const TILE_SIZE: f32 = 1.0;
+    /// Tiles are lowered by their height, so their top is the ground plane at y = 0.
+    pub const TILE_HEIGHT: f32 = 0.2;
+
+    pub fn from_map(map: &Map) -> Self {
+        let mut tiles = BTreeMap::new();
+        if let Some(ground) = &map.ground {
+            for x in ground.min.0..=ground.max.0 {
+                for z in ground.min.1..=ground.max.1 {
+                    tiles.insert(
+                        (x, z),
+                        Tile {
+                            model: ground.model.clone(),
+                            rotation: 0,
+                            properties: TileProperties::of(&ground.model),
+                        },
+                    );
+                }
+            }
+        }
+        let cell = |tile: &MapTile| {
+            (
+                tile.position,
+                Tile {
+                    model: tile.model.clone(),
+                    rotation: tile.rotation,
+                    properties: TileProperties::of(&tile.model),
+                },
+            )
+        };
+        tiles.extend(map.tiles.iter().map(cell));
+        Terrain {
+            tiles,
+            details: map.details.iter().map(cell).collect(),
+        }
+    }
+
+    /// Grid cell containing `position`.
+    pub fn cell(position: Vec3) -> (i32, i32) {
+        let cell = (position.xz() / Self::TILE_SIZE).round();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    pub fn cell_center(cell: (i32, i32)) -> Vec3 {
+        Vec3::new(cell.0 as f32, 0.0, cell.1 as f32) * Self::TILE_SIZE
+    }
+
+    pub fn tile_at(&self, position: Vec3) -> Option<&Tile> {
+        self.tiles.get(&Self::cell(position))
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = ((i32, i32), &Tile)> {
+        self.tiles.iter().map(|(cell, tile)| (*cell, tile))
+    }
+
+    pub fn details(&self) -> impl Iterator<Item = ((i32, i32), &Tile)> {
+        self.details.iter().map(|(cell, tile)| (*cell, tile))
+    }
+
+    fn properties_at(&self, position: Vec3) -> TileProperties {
+        if self.tiles.is_empty() {
+            return TileProperties::OPEN;
+        }
+        let properties =
+            self.tile_at(position).map_or(TileProperties::BLOCKED, |tile| tile.properties);
+        match self.details.get(&Self::cell(position)) {
+            Some(detail) => TileProperties {
+                walkable: properties.walkable && detail.properties.walkable,
+                buildable: properties.buildable && detail.properties.buildable,
+            },
+            None => properties,
+        }
+    }
+
+    pub fn is_walkable(&self, position: Vec3) -> bool {
+        self.properties_at(position).walkable
+    }
+
+    pub fn is_buildable(&self, position: Vec3) -> bool {
+        self.properties_at(position).buildable
+    }
+
+    /// Whether every tile the path crosses is walkable.
+    pub fn is_walkable_along(&self, path: &WayPath) -> bool {
+        let step = Self::TILE_SIZE / 4.0;
+        let samples = (path.length() / step).ceil() as usize;
+        (0..=samples).all(|i| self.is_walkable(path.sample(i as f32 * step, false).0))
+    }
+
+    /// Respawns the tile and detail scenes whenever the terrain changes, e.g. when a map is
+    /// started.
+    pub fn insert_scenes(
+        mut commands: Commands,
+        terrain: Res<Terrain>,
+        asset_server: Res<AssetServer>,
+        q_tiles: Query<Entity, With<TerrainTile>>,
+    ) {
+        if !terrain.is_changed() {
+            return;
+        }
+        for entity in q_tiles.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        // details stand on the ground plane, on top of their tile
+        let tiles = terrain.tiles().map(|tile| (tile, -Self::TILE_HEIGHT));
+        for ((cell, tile), height) in tiles.chain(terrain.details().map(|detail| (detail, 0.0))) {
+            commands.spawn((
+                TerrainTile,
+                SceneBundle {
+                    scene: asset_server.load(format!("models/{}.glb#Scene0", tile.model)),
+                    transform: Transform::from_translation(
+                        Self::cell_center(cell) + Vec3::Y * height,
+                    )
+                    .with_rotation(Quat::from_rotation_y(tile.rotation as f32 * FRAC_PI_2)),
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+/// Scene of a [`Tile`].
+#[derive(Component)]
+pub struct TerrainTile;
@@ -1,101 +1,395 @@
+use std::{collections::BTreeMap, sync::Arc};
+
 use bevy::prelude::*;
 
-use crate::building::Building;
+use crate::{
+    assets::AssetController,
+    building::Building,
+    economy::Cargo,
+    game::SimulationSet,
+    player::Owner,
+    replay::SimulationId,
+    routing::WayGraph,
+    unit_kind::{UnitKind, UnitKinds},
+    way::{Way, WayPath},
+};
+
 pub struct UnitPlugin;
 
 impl Plugin for UnitPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<SpawnUnit>()
+        app.register_type::<UnitKinds>()
+            .init_resource::<UnitKinds>()
+            .init_resource::<WaitingUnits>()
+            .add_event::<SpawnUnit>()
             .add_event::<UnitArrived>()
-            .add_systems(Startup, UnitSpawner::setup)
-            .add_systems(PreUpdate, SpawnUnit::handle)
-            .add_systems(Update, Unit::update);
+            .add_systems(
+                FixedUpdate,
+                (
+                    SpawnUnit::handle.in_set(SimulationSet::Spawn),
+                    Unit::update.in_set(SimulationSet::Update),
+                ),
+            );
     }
 }
 
-#[derive(Resource)]
-pub struct UnitSpawner {
-    pub scene: Handle<Scene>,
-}
+pub struct UnitVisualsPlugin;
 
-impl UnitSpawner {
-    pub fn setup(mut commands: Commands, asset_server: ResMut<AssetServer>) {
-        let scene = asset_server.load("models/unit.glb#Scene0");
-
-        commands.insert_resource(UnitSpawner {
-            scene,
-        });
+impl Plugin for UnitVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, Unit::insert_scene);
     }
 }
 
-#[derive(Event, Debug)]
+/// Sends a new unit from `from_building` along the shortest route to `destination`, as soon as
+/// there is room on the first way.
+#[derive(Event, Debug, Clone)]
 pub struct SpawnUnit {
-    pub unit: Unit,
+    pub kind: UnitKind,
+    pub from_building: Entity,
+    pub destination: Entity,
+    pub home: Entity,
+    pub cargo: Option<Cargo>,
+}
+
+/// Units that could not enter their first way yet, because it is full or the last unit that
+/// entered it is still too close. They enter in the order they were requested.
+#[derive(Resource, Debug, Default)]
+pub struct WaitingUnits(pub Vec<SpawnUnit>);
+
+impl WaitingUnits {
+    pub fn at(&self, building: Entity) -> usize {
+        self.0.iter().filter(|waiting| waiting.from_building == building).count()
+    }
 }
 
 impl SpawnUnit {
     pub fn handle(
         mut commands: Commands,
-        unit_spawner: Res<UnitSpawner>,
         mut spawn_unit: EventReader<SpawnUnit>,
-        q_buildings: Query<&Transform, With<Building>>,
+        mut waiting: ResMut<WaitingUnits>,
+        q_ways: Query<(Entity, &Way, &SimulationId)>,
+        q_units: Query<(Entity, &Unit, &SimulationId)>,
+        q_owners: Query<&Owner>,
+        unit_kinds: Res<UnitKinds>,
     ) {
-        for event in spawn_unit.read() {
-            info!(target: "events", "{:?}", event);
-            let from_building = q_buildings.get(event.unit.from_building).unwrap();
-            let to_building = q_buildings.get(event.unit.to_building).unwrap();
-            let direction = to_building.translation - from_building.translation;
-            commands.spawn((
-                event.unit.clone(),
-                SceneBundle {
-                    scene: unit_spawner.scene.clone(),
-                    transform: Transform {
-                        translation: from_building.translation,
-                        scale: Vec3::splat(0.2),
+        let graph = WayGraph::new(q_ways.iter());
+        let mut lanes = Lanes::new(q_units.iter());
+        let requests: Vec<_> = std::mem::take(&mut waiting.0)
+            .into_iter()
+            .chain(spawn_unit.read().map(|event| {
+                info!(target: "events", "{:?}", event);
+                event.clone()
+            }))
+            .collect();
+        for event in requests {
+            let Some(mut route) = graph.shortest_path(event.from_building, event.destination)
+            else {
+                continue;
+            };
+            if route.is_empty() {
+                continue;
+            }
+            let to_building = route.remove(0);
+            let Some((way_entity, way, _)) = graph
+                .way_between(event.from_building, to_building)
+                .and_then(|entity| q_ways.get(entity).ok())
+            else {
+                continue;
+            };
+            let reversed = way.to == event.from_building;
+            let lane = (way_entity, reversed);
+            if !lanes.has_room(lane, way.capacity()) {
+                waiting.0.push(event);
+                continue;
+            }
+            let (position, direction) = way.path.sample(0.0, reversed);
+            let stats = unit_kinds.get(event.kind);
+            let mut unit = commands.spawn((
+                Unit {
+                    kind: event.kind,
+                    health: stats.health,
+                    from_building: event.from_building,
+                    to_building,
+                    destination: event.destination,
+                    route,
+                    home: event.home,
+                    cargo: event.cargo,
+                    fighting: None,
+                    way: way_entity,
+                    path: way.path.clone(),
+                    reversed,
+                    distance: 0.0,
+                },
+                TransformBundle::from_transform(
+                    Transform {
+                        translation: position,
+                        scale: Vec3::splat(stats.scale),
                         ..default()
                     }
                     .looking_to(direction, Vec3::Y),
-                    ..default()
-                },
+                ),
             ));
+            // units belong to whoever owns the building they were sent from
+            if let Ok(owner) = q_owners.get(event.home) {
+                unit.insert(*owner);
+            }
+            lanes.enter(lane, unit.id());
         }
     }
 }
 
 #[derive(Component, Clone, Debug)]
 pub struct Unit {
+    pub kind: UnitKind,
+    pub health: u32,
+    /// Building the unit left last.
     pub from_building: Entity,
+    /// Building the unit is heading to next.
     pub to_building: Entity,
+    /// Building the unit only stops at once it gets there.
+    pub destination: Entity,
+    /// Buildings left to pass after [`Unit::to_building`], ending with [`Unit::destination`].
+    /// Cleared when the unit has to turn around, so it is computed again at the next building.
+    pub route: Vec<Entity>,
+    /// Building the unit was originally sent from, which harvested resources are brought to.
+    pub home: Entity,
+    pub cargo: Option<Cargo>,
+    /// Enemy unit this one fights, it stands still until either of them is dead or out of range.
+    pub fighting: Option<Entity>,
+    /// The way the unit is currently walking along.
+    pub way: Entity,
+    /// Path of [`Unit::way`], kept even if the way is removed while the unit is on it.
+    pub path: Arc<WayPath>,
+    /// Whether the unit walks the way from [`Way::to`] to [`Way::from`].
+    pub reversed: bool,
+    /// How far the unit got along the way since leaving [`Unit::from_building`].
+    pub distance: f32,
 }
 
 impl Unit {
+    /// Turns the unit around to the building it came from, e.g. because its way was removed.
+    pub fn turn_around(&mut self) {
+        std::mem::swap(&mut self.from_building, &mut self.to_building);
+        self.route.clear();
+        self.reversed = !self.reversed;
+        self.distance = self.path.length() - self.distance;
+    }
+
+    pub fn lane(&self) -> Lane {
+        (self.way, self.reversed)
+    }
+
+    /// The way to take from [`Unit::to_building`] and the building it leads to, following the
+    /// stored route while it is intact and looking for a new one otherwise. `None` if the unit
+    /// has to stop there because it arrived or its destination is unreachable.
+    fn next_hop(&mut self, graph: &WayGraph) -> Option<(Entity, Entity)> {
+        let current = self.to_building;
+        if current == self.destination {
+            return None;
+        }
+        let route_intact =
+            self.route.first().is_some_and(|next| graph.way_between(current, *next).is_some());
+        if !route_intact {
+            match graph.shortest_path(current, self.destination) {
+                Some(route) if !route.is_empty() => self.route = route,
+                _ => return None,
+            }
+        }
+        let next = self.route[0];
+        Some((graph.way_between(current, next)?, next))
+    }
+
+    fn enter_way(&mut self, way_entity: Entity, way: &Way, next: Entity) {
+        let current = self.to_building;
+        self.route.remove(0);
+        self.from_building = current;
+        self.to_building = next;
+        self.way = way_entity;
+        self.path = way.path.clone();
+        self.reversed = way.to == current;
+        self.distance = 0.0;
+    }
+
+    /// Moves units along their lanes front to back, so they queue behind each other and wait at
+    /// buildings until there is room on the next way.
     pub fn update(
         mut commands: Commands,
         time: Res<Time>,
-        mut q_units: Query<(Entity, &Unit, &mut Transform), Without<Building>>,
-        q_buildings: Query<&Transform, With<Building>>,
+        mut q_units: Query<
+            (Entity, &mut Unit, &mut Transform, Option<&Owner>, &SimulationId),
+            Without<Building>,
+        >,
+        q_buildings: Query<(), With<Building>>,
+        q_ways: Query<(Entity, &Way, &SimulationId)>,
+        unit_kinds: Res<UnitKinds>,
         mut ev_unit_arrived: EventWriter<UnitArrived>,
     ) {
-        for (entity, unit, mut transform) in q_units.iter_mut() {
-            let to_building = q_buildings.get(unit.to_building).unwrap();
-            let direction = to_building.translation - transform.translation;
-            let distance = direction.length();
-            let speed = 1.0;
-            let direction = direction.normalize();
-            let movement = direction * speed * time.delta_seconds();
-            if distance > movement.length() {
-                transform.translation += movement;
-            } else {
-                ev_unit_arrived.send(UnitArrived {
-                    building: unit.to_building,
-                });
-                commands.entity(entity).despawn();
+        let graph = WayGraph::new(q_ways.iter());
+        let mut lanes = Lanes::new(q_units.iter().map(|(entity, unit, .., id)| (entity, unit, id)));
+        let capacity =
+            |(way, _): Lane| q_ways.get(way).map_or(usize::MAX, |(_, way, _)| way.capacity());
+
+        for entity in lanes.units_front_first() {
+            let (entity, mut unit, mut transform, owner, _) = q_units.get_mut(entity).unwrap();
+            let lane = unit.lane();
+            if !q_buildings.contains(unit.to_building) {
+                // the building was despawned before the unit could turn around
+                lanes.remove(lane, entity);
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+            if unit.fighting.is_some() {
+                continue;
+            }
+
+            // units slow down once their lane is more than half full, to half speed when full
+            let load = lanes.len(lane) as f32 / capacity(lane) as f32;
+            let speed = unit_kinds.get(unit.kind).speed * (1.5 - load.clamp(0.5, 1.0));
+            let limit = lanes
+                .ahead_of(lane, entity)
+                .map_or(f32::INFINITY, |ahead| ahead - Way::UNIT_SPACING);
+            let distance = (unit.distance + speed * time.delta_seconds()).min(limit);
+            unit.distance = distance.max(unit.distance);
+            lanes.set(lane, entity, unit.distance);
+
+            if unit.distance >= unit.path.length() {
+                match unit.next_hop(&graph) {
+                    Some((way_entity, next)) => {
+                        let (_, way, _) = q_ways.get(way_entity).unwrap();
+                        let next_lane = (way_entity, way.to == unit.to_building);
+                        if lanes.has_room(next_lane, way.capacity()) {
+                            unit.enter_way(way_entity, way, next);
+                            lanes.remove(lane, entity);
+                            lanes.enter(next_lane, entity);
+                        } else {
+                            unit.distance = unit.path.length();
+                        }
+                    }
+                    None => {
+                        ev_unit_arrived.send(UnitArrived {
+                            building: unit.to_building,
+                            unit: unit.clone(),
+                            owner: owner.copied(),
+                        });
+                        lanes.remove(lane, entity);
+                        commands.entity(entity).despawn_recursive();
+                        continue;
+                    }
+                }
+            }
+            let (position, direction) = unit.path.sample(unit.distance, unit.reversed);
+            transform.translation = position;
+            if direction != Vec3::ZERO {
+                transform.look_to(direction, Vec3::Y);
             }
         }
     }
+
+    pub fn insert_scene(
+        mut commands: Commands,
+        asset_controller: Res<AssetController>,
+        q_units: Query<(Entity, &Unit), Added<Unit>>,
+    ) {
+        for (entity, unit) in q_units.iter() {
+            commands.entity(entity).insert((
+                asset_controller.unit_scenes[&unit.kind].clone(),
+                VisibilityBundle::default(),
+            ));
+        }
+    }
 }
 
+/// Units on the same way that walk in the same direction, identified by the way and whether it
+/// is walked reversed.
+pub type Lane = (Entity, bool);
+
+/// Positions of all units on their lanes.
+#[derive(Debug, Default)]
+pub struct Lanes {
+    /// Units with their distance along the lane, the one furthest ahead first.
+    lanes: BTreeMap<Lane, Vec<(Entity, f32)>>,
+    /// The lanes in the order of the ids of the units that were in front when they were created.
+    order: Vec<Lane>,
+}
+
+impl Lanes {
+    /// Ties between units are broken by their [`SimulationId`], so they move in the same order
+    /// in every run.
+    pub fn new<'a>(units: impl IntoIterator<Item = (Entity, &'a Unit, &'a SimulationId)>) -> Self {
+        let mut lanes: BTreeMap<Lane, Vec<(Entity, SimulationId, f32)>> = BTreeMap::new();
+        for (entity, unit, id) in units {
+            lanes.entry(unit.lane()).or_default().push((entity, *id, unit.distance));
+        }
+        for units in lanes.values_mut() {
+            units.sort_by(|(_, a, a_distance), (_, b, b_distance)| {
+                b_distance.total_cmp(a_distance).then(a.cmp(b))
+            });
+        }
+        let mut order: Vec<_> = lanes.iter().map(|(lane, units)| (units[0].1, *lane)).collect();
+        order.sort_by_key(|(front, _)| *front);
+        Lanes {
+            order: order.into_iter().map(|(_, lane)| lane).collect(),
+            lanes: lanes
+                .into_iter()
+                .map(|(lane, units)| {
+                    (
+                        lane,
+                        units.into_iter().map(|(entity, _, distance)| (entity, distance)).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn len(&self, lane: Lane) -> usize {
+        self.lanes.get(&lane).map_or(0, Vec::len)
+    }
+
+    /// Whether another unit may enter the lane at its start.
+    pub fn has_room(&self, lane: Lane, capacity: usize) -> bool {
+        let units = self.lanes.get(&lane).map_or(&[][..], Vec::as_slice);
+        units.len() < capacity
+            && !units.last().is_some_and(|(_, distance)| *distance < Way::UNIT_SPACING)
+    }
+
+    fn units_front_first(&self) -> Vec<Entity> {
+        self.order.iter().flat_map(|lane| &self.lanes[lane]).map(|(entity, _)| *entity).collect()
+    }
+
+    /// Distance of the unit right in front of `unit`.
+    fn ahead_of(&self, lane: Lane, unit: Entity) -> Option<f32> {
+        let units = self.lanes.get(&lane)?;
+        let index = units.iter().position(|(entity, _)| *entity == unit)?;
+        Some(units.get(index.checked_sub(1)?)?.1)
+    }
+
+    fn set(&mut self, lane: Lane, unit: Entity, distance: f32) {
+        if let Some(entry) = self
+            .lanes
+            .get_mut(&lane)
+            .and_then(|units| units.iter_mut().find(|(entity, _)| *entity == unit))
+        {
+            entry.1 = distance;
+        }
+    }
+
+    fn remove(&mut self, lane: Lane, unit: Entity) {
+        if let Some(units) = self.lanes.get_mut(&lane) {
+            units.retain(|(entity, _)| *entity != unit);
+        }
+    }
+
+    fn enter(&mut self, lane: Lane, unit: Entity) {
+        self.lanes.entry(lane).or_default().push((unit, 0.0));
+    }
+}
+
+/// Sent when a unit stops at a building, which is its destination unless that became unreachable.
 #[derive(Event)]
 pub struct UnitArrived {
     pub building: Entity,
+    /// The unit as it was before despawning.
+    pub unit: Unit,
+    pub owner: Option<Owner>,
 }
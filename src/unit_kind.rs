@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The different units buildings can produce, with their stats in [`UnitKinds`].
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Default,
+    Reflect,
+    Serialize,
+    Deserialize,
+)]
+pub enum UnitKind {
+    /// Harvests resources.
+    #[default]
+    Worker,
+    Soldier,
+    /// Fast, but fragile and carries nothing.
+    Scout,
+    /// Slow, but carries a lot.
+    Carrier,
+}
+
+impl UnitKind {
+    pub const ALL: [UnitKind; 4] =
+        [UnitKind::Worker, UnitKind::Soldier, UnitKind::Scout, UnitKind::Carrier];
+
+    /// The kind after this one in [`UnitKind::ALL`], wrapping around.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Debug, Clone, Reflect)]
+pub struct UnitStats {
+    /// Distance walked per second on an empty way.
+    pub speed: f32,
+    pub health: u32,
+    /// Damage dealt per combat round, 0 if it can't fight.
+    pub attack: u32,
+    /// Resources a unit harvests and carries at once, 0 if it can't harvest.
+    pub capacity: u32,
+    /// Scene asset path.
+    pub model: String,
+    pub scale: f32,
+}
+
+/// Stats of every [`UnitKind`], which movement, spawning, harvesting and combat are based on.
+#[derive(Resource, Debug, Clone, Reflect)]
+pub struct UnitKinds {
+    stats: [UnitStats; UnitKind::ALL.len()],
+}
+
+impl UnitKinds {
+    pub fn get(&self, kind: UnitKind) -> &UnitStats {
+        &self.stats[kind as usize]
+    }
+
+    pub fn get_mut(&mut self, kind: UnitKind) -> &mut UnitStats {
+        &mut self.stats[kind as usize]
+    }
+}
+
+impl Default for UnitKinds {
+    fn default() -> Self {
+        let stats = |speed, health, attack, capacity, model: &str, scale| UnitStats {
+            speed,
+            health,
+            attack,
+            capacity,
+            model: model.to_string(),
+            scale,
+        };
+        UnitKinds {
+            stats: [
+                stats(1.0, 10, 1, 1, "models/unit.glb#Scene0", 0.2),
+                stats(0.8, 30, 5, 0, "models/enemy_ufoRedWeapon.glb#Scene0", 0.25),
+                stats(2.0, 5, 1, 0, "models/enemy_ufoGreen.glb#Scene0", 0.15),
+                stats(0.6, 20, 0, 5, "models/enemy_ufoYellow.glb#Scene0", 0.3),
+            ],
+        }
+    }
+}
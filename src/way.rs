@@ -1,99 +1,476 @@
-use bevy::{
-    prelude::*,
-    render::{
-        mesh::{Indices, VertexAttributeValues},
-        render_asset::RenderAssetUsages,
-        render_resource::PrimitiveTopology,
-    },
-};
-use bevy_xpbd_3d::plugins::{
-    collision::Collider,
-    spatial_query::{SpatialQuery, SpatialQueryFilter},
-};
+use std::sync::Arc;
 
 use crate::{
-    building::Building,
+    building::{construction::BuildMode, Building, DespawnBuilding},
+    combat::BuildingCaptured,
+    game::{RestartMatch, SimulationSet},
     input::{InputController, InputEvent},
+    placement::Placement,
+    player::{LocalPlayer, Owner, Players},
+    unit::Unit,
+};
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology},
 };
 
 pub struct WayPlugin;
 
 impl Plugin for WayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, WayController::setup).add_event::<InteractWay>().add_systems(
+        app.register_type::<WayDirection>()
+            .add_event::<InteractWay>()
+            .add_systems(FixedUpdate, InteractWay::handle.in_set(SimulationSet::Interact));
+    }
+}
+
+pub struct WayVisualsPlugin;
+
+impl Plugin for WayVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, (WayController::setup, FlowLabel::setup)).add_systems(
             Update,
-            (WayController::handle_input, InteractWay::handle, PlacingWay::update).chain(),
+            (
+                (
+                    WayController::handle_input,
+                    WayController::abort_with_building,
+                    InteractWay::handle_placing,
+                    PlacingWay::update,
+                )
+                    .chain(),
+                Way::insert_mesh,
+                Way::draw_direction,
+                FlowLabel::update,
+            ),
         );
     }
 }
 
+/// Connection between two buildings that units travel along.
+#[derive(Component, Debug, Clone)]
+pub struct Way {
+    pub from: Entity,
+    pub to: Entity,
+    pub direction: WayDirection,
+    /// Share of the units leaving either building along this way, relative to the weights of the
+    /// building's other ways. Ways with weight 0 get no units.
+    pub weight: u32,
+    /// Shared with the units on the way, so they can finish walking it after it was removed.
+    pub path: Arc<WayPath>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+pub enum WayDirection {
+    /// Units only travel from [`Way::from`] to [`Way::to`].
+    Forward,
+    /// Units only travel from [`Way::to`] to [`Way::from`].
+    Backward,
+    #[default]
+    Both,
+}
+
+impl WayDirection {
+    /// The direction after toggling it once.
+    pub fn toggled(self) -> Self {
+        match self {
+            WayDirection::Forward => WayDirection::Backward,
+            WayDirection::Backward => WayDirection::Both,
+            WayDirection::Both => WayDirection::Forward,
+        }
+    }
+}
+
+impl Way {
+    pub const DEFAULT_WEIGHT: u32 = 1;
+    pub const MAX_WEIGHT: u32 = 9;
+    pub const WIDTH: f32 = 1.0;
+    /// Minimum distance between units walking the same way in the same direction.
+    pub const UNIT_SPACING: f32 = 0.5;
+
+    pub fn new(from: Entity, to: Entity, path: WayPath) -> Self {
+        Way {
+            from,
+            to,
+            direction: WayDirection::default(),
+            weight: Way::DEFAULT_WEIGHT,
+            path: Arc::new(path),
+        }
+    }
+
+    /// How many units fit on the way in each direction.
+    pub fn capacity(&self) -> usize {
+        ((self.path.length() / Self::UNIT_SPACING) as usize).max(1)
+    }
+
+    pub fn connects(&self, building: Entity, other: Entity) -> bool {
+        (self.from == building && self.to == other) || (self.from == other && self.to == building)
+    }
+
+    /// The building at the other end, if units may travel there from `building`.
+    pub fn destination_from(&self, building: Entity) -> Option<Entity> {
+        match self.direction {
+            WayDirection::Forward | WayDirection::Both if self.from == building => Some(self.to),
+            WayDirection::Backward | WayDirection::Both if self.to == building => Some(self.from),
+            _ => None,
+        }
+    }
+
+    pub fn insert_mesh(
+        mut commands: Commands,
+        controller: Res<WayController>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        q_ways: Query<(Entity, &Way), Added<Way>>,
+    ) {
+        for (entity, way) in q_ways.iter() {
+            commands.entity(entity).insert(PbrBundle {
+                mesh: meshes.add(way.path.ribbon_mesh(Way::WIDTH)),
+                material: controller.material.clone(),
+                ..default()
+            });
+        }
+    }
+
+    /// Shows in which directions units may travel, highlighting the hovered way.
+    pub fn draw_direction(
+        mut gizmos: Gizmos,
+        input_controller: Res<InputController>,
+        q_ways: Query<(Entity, &Way)>,
+    ) {
+        for (entity, way) in q_ways.iter() {
+            let color = if input_controller.hovering_way == Some(entity) {
+                Color::WHITE
+            } else {
+                Color::rgb(0.6, 0.9, 0.6)
+            };
+            let (center, direction) = way.path.sample(way.path.length() / 2.0, false);
+            let center = center + Vec3::Y * 0.05;
+            let half = direction * 0.4;
+            if way.destination_from(way.from).is_some() {
+                gizmos.arrow(center - half, center + half, color);
+            }
+            if way.destination_from(way.to).is_some() {
+                gizmos.arrow(center + half, center - half, color);
+            }
+        }
+    }
+}
+
+/// Polyline units walk along, from [`Way::from`] to [`Way::to`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct WayPath {
+    points: Vec<Vec3>,
+    /// Length of the path up to each point.
+    distances: Vec<f32>,
+}
+
+impl WayPath {
+    /// Needs at least two points.
+    pub fn new(points: Vec<Vec3>) -> Self {
+        assert!(points.len() >= 2, "a way path needs a start and an end");
+        let mut distances = Vec::with_capacity(points.len());
+        let mut distance = 0.0;
+        distances.push(distance);
+        for segment in points.windows(2) {
+            distance += segment[0].distance(segment[1]);
+            distances.push(distance);
+        }
+        WayPath {
+            points,
+            distances,
+        }
+    }
+
+    pub fn straight(from: Vec3, to: Vec3) -> Self {
+        WayPath::new(vec![from, to])
+    }
+
+    /// Joins consecutive parts as returned by [`curve_segments`].
+    pub fn from_segments(segments: &[Vec<Vec3>]) -> Self {
+        let mut points = segments[0].clone();
+        for segment in &segments[1..] {
+            points.extend_from_slice(&segment[1..]);
+        }
+        WayPath::new(points)
+    }
+
+    /// The path of a way from `from` to `to` bending through `waypoints`.
+    pub fn curved(from: Vec3, waypoints: &[Vec3], to: Vec3) -> Self {
+        let mut control_points = vec![from];
+        control_points.extend_from_slice(waypoints);
+        control_points.push(to);
+        WayPath::from_segments(&curve_segments(&control_points))
+    }
+
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.distances.last().unwrap()
+    }
+
+    /// Position and direction of travel `distance` along the path, measured from the end when
+    /// walking it `reversed`.
+    pub fn sample(&self, distance: f32, reversed: bool) -> (Vec3, Vec3) {
+        let distance = distance.clamp(0.0, self.length());
+        let distance = if reversed { self.length() - distance } else { distance };
+        let segment = self
+            .distances
+            .partition_point(|start| *start <= distance)
+            .saturating_sub(1)
+            .min(self.points.len() - 2);
+        let (start, end) = (self.points[segment], self.points[segment + 1]);
+        let segment_length = self.distances[segment + 1] - self.distances[segment];
+        let t = if segment_length > 0.0 {
+            (distance - self.distances[segment]) / segment_length
+        } else {
+            0.0
+        };
+        let direction = (end - start).normalize_or_zero();
+        (start.lerp(end, t), if reversed { -direction } else { direction })
+    }
+
+    /// Shortest distance from `point` to the path, ignoring height.
+    pub fn distance_xz(&self, point: Vec2) -> f32 {
+        self.points
+            .windows(2)
+            .map(|segment| {
+                let (start, end) = (segment[0].xz(), segment[1].xz());
+                let along = end - start;
+                let t = if along.length_squared() > 0.0 {
+                    ((point - start).dot(along) / along.length_squared()).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                point.distance(start + along * t)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Whether a strip of `width` along the path overlaps `rect` on the ground, sampled like
+    /// [`Terrain::is_walkable_along`](crate::terrain::Terrain::is_walkable_along). Merely
+    /// touching it is fine.
+    pub fn overlaps(&self, rect: Rect, width: f32) -> bool {
+        let step = width / 4.0;
+        let samples = (self.length() / step).ceil() as usize;
+        let (min, max) = (rect.min - width / 2.0, rect.max + width / 2.0);
+        (0..=samples).any(|i| {
+            let point = self.sample(i as f32 * step, false).0.xz();
+            point.cmpgt(min).all() && point.cmplt(max).all()
+        })
+    }
+
+    /// Flat strip of `width` along the path, in world space.
+    pub fn ribbon_mesh(&self, width: f32) -> Mesh {
+        let count = self.points.len();
+        let mut positions = Vec::with_capacity(count * 2);
+        let mut uvs = Vec::with_capacity(count * 2);
+        for (index, point) in self.points.iter().enumerate() {
+            let tangent =
+                self.points[(index + 1).min(count - 1)] - self.points[index.saturating_sub(1)];
+            let offset = tangent.try_normalize().unwrap_or(Vec3::X).cross(Vec3::Y) * width / 2.0;
+            positions.push((*point + offset).to_array());
+            positions.push((*point - offset).to_array());
+            uvs.push([0.0, self.distances[index]]);
+            uvs.push([1.0, self.distances[index]]);
+        }
+        let indices = (0..count as u32 - 1)
+            .flat_map(|segment| {
+                let left = segment * 2;
+                [left, left + 2, left + 1, left + 1, left + 2, left + 3]
+            })
+            .collect();
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; positions.len()])
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+            .with_inserted_indices(Indices::U32(indices))
+    }
+}
+
+/// Picks the way for the next unit a building sends, so that over time each way gets units in
+/// proportion to its weight, interleaved as evenly as possible (smooth weighted round-robin).
+#[derive(Debug, Default, Clone)]
+pub struct FlowDistributor {
+    /// Accumulated weight per way, only ever changed by [`FlowDistributor::next`].
+    current: Vec<(Entity, i64)>,
+}
+
+impl FlowDistributor {
+    /// Takes `(way, weight)` pairs of all ways in a stable order and returns the chosen one of
+    /// those that are `open`, or `None` if all of their weights are 0. Ways that are not open,
+    /// like full ones, keep their accumulated weight until they are open again.
+    pub fn next(
+        &mut self,
+        ways: &[(Entity, u32)],
+        open: impl Fn(Entity) -> bool,
+    ) -> Option<Entity> {
+        // only forget ways that are gone
+        self.current.retain(|(way, _)| ways.iter().any(|(other, _)| other == way));
+        let open: Vec<_> = ways.iter().copied().filter(|(way, _)| open(*way)).collect();
+        let total: i64 = open.iter().map(|(_, weight)| i64::from(*weight)).sum();
+        if total == 0 {
+            return None;
+        }
+        for (way, weight) in &open {
+            match self.current.iter_mut().find(|(other, _)| other == way) {
+                Some((_, current)) => *current += i64::from(*weight),
+                None => self.current.push((*way, i64::from(*weight))),
+            }
+        }
+        let current = |way: Entity| {
+            self.current.iter().find(|(other, _)| *other == way).map_or(0, |(_, current)| *current)
+        };
+        // the first way wins ties
+        let (_, (chosen, _)) = open
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, (way, _))| (current(*way), std::cmp::Reverse(*index)))?;
+        let chosen = *chosen;
+        for (way, current) in &mut self.current {
+            if *way == chosen {
+                *current -= total;
+            }
+        }
+        Some(chosen)
+    }
+}
+
+/// Text showing the weight of the hovered way and which share of the units it gets.
+#[derive(Component)]
+pub struct FlowLabel;
+
+impl FlowLabel {
+    pub fn setup(mut commands: Commands) {
+        commands.spawn((
+            FlowLabel,
+            TextBundle::from_section("", TextStyle::default()).with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(12.0),
+                left: Val::Px(12.0),
+                ..default()
+            }),
+        ));
+    }
+
+    pub fn update(
+        input_controller: Res<InputController>,
+        q_ways: Query<&Way>,
+        q_buildings: Query<&Building>,
+        mut q_label: Query<&mut Text, With<FlowLabel>>,
+    ) {
+        let Ok(mut text) = q_label.get_single_mut() else {
+            return;
+        };
+        let Some((entity, way)) = input_controller
+            .hovering_way
+            .and_then(|entity| Some((entity, q_ways.get(entity).ok()?)))
+        else {
+            text.sections[0].value.clear();
+            return;
+        };
+        let mut value = format!("Flow weight {} (+/- to change)", way.weight);
+        for (end, building) in [("start", way.from), ("end", way.to)] {
+            let Ok(building_component) = q_buildings.get(building) else {
+                continue;
+            };
+            if let Some((_, share)) = building_component
+                .flow_shares(building, &q_ways)
+                .into_iter()
+                .find(|(way, _)| *way == entity)
+            {
+                value += &format!("\n{:.0}% of the units leaving the {end}", share * 100.0);
+            }
+        }
+        text.sections[0].value = value;
+    }
+}
+
+/// Points the Catmull-Rom spline through `control_points` passes, split into the parts between
+/// two consecutive control points. Each part starts and ends at its control points, and parts
+/// between just two control points are straight.
+pub fn curve_segments(control_points: &[Vec3]) -> Vec<Vec<Vec3>> {
+    const SAMPLES: usize = 8;
+
+    if control_points.len() <= 2 {
+        return vec![control_points.to_vec()];
+    }
+    let last = control_points.len() - 1;
+    (0..last)
+        .map(|index| {
+            let (p1, p2) = (control_points[index], control_points[index + 1]);
+            // mirror the neighbours at the ends so the curve keeps its direction there
+            let p0 = if index == 0 { 2.0 * p1 - p2 } else { control_points[index - 1] };
+            let p3 = if index + 1 == last { 2.0 * p2 - p1 } else { control_points[index + 2] };
+            (0..=SAMPLES)
+                .map(|sample| {
+                    let t = sample as f32 / SAMPLES as f32;
+                    0.5 * (2.0 * p1
+                        + (p2 - p0) * t
+                        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t
+                        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t * t * t)
+                })
+                .collect()
+        })
+        .collect()
+}
+
 #[derive(Resource)]
 pub struct WayController {
     pub material: Handle<StandardMaterial>,
-    pub mesh: Handle<Mesh>,
     pub start_building: Option<Entity>,
-    pub connected: Vec<(Entity, Entity)>,
+    /// Ground points clicked while placing, which the way will bend through.
+    pub waypoints: Vec<Vec3>,
+    /// Whether the placed way would reach the hovered building without crossing anything.
     pub placing_valid: bool,
+    /// Whether nothing is in the way between the start building and the cursor.
+    pub path_clear: bool,
 }
 
 impl WayController {
-    pub fn setup(
-        mut commands: Commands,
-        mut materials: ResMut<Assets<StandardMaterial>>,
-        mut meshes: ResMut<Assets<Mesh>>,
-    ) {
+    pub fn setup(mut commands: Commands, mut materials: ResMut<Assets<StandardMaterial>>) {
         let material = materials.add(Color::rgb(0.3, 0.5, 0.3));
-        let base_mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
-            .with_inserted_attribute(
-                Mesh::ATTRIBUTE_POSITION,
-                //vec![[-0.5, 0.0, -0.5], [0.5, 0.0, -0.5], [0.5, 0.0, 0.5], [-0.5, 0.0, 0.5]],
-                vec![[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]],
-            )
-            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 4])
-            .with_inserted_attribute(
-                Mesh::ATTRIBUTE_UV_0,
-                vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
-            )
-            .with_inserted_indices(Indices::U32(vec![0, 3, 1, 1, 3, 2]));
-        let mesh = meshes.add(base_mesh);
 
         commands.insert_resource(WayController {
             material: material.clone(),
-            mesh: mesh.clone(),
             start_building: None,
+            waypoints: Vec::new(),
             placing_valid: false,
-            connected: Vec::new(),
+            path_clear: false,
         });
     }
 
-    fn handle_input(
+    /// Places ways by clicking on the buildings to connect and the ground in between. Clicking
+    /// the second building requests the way through
+    /// [`input::issue_commands`](crate::input::issue_commands), placing ends once the simulation
+    /// built it.
+    pub fn handle_input(
         mut ev_input: EventReader<InputEvent>,
         mut ev_interact_way: EventWriter<InteractWay>,
-        q_building: Query<&Building>,
-        controller: Res<WayController>,
+        mut controller: ResMut<WayController>,
+        build_mode: Option<Res<BuildMode>>,
+        q_owners: Query<&Owner>,
+        local_player: Res<LocalPlayer>,
+        players: Res<Players>,
     ) {
+        // clicks place the building instead
+        let placing_building = build_mode.is_some_and(|build_mode| build_mode.is_active());
         for event in ev_input.read() {
             match *event {
                 InputEvent::ClickedOnBuilding {
                     building,
-                } => {
-                    if let Some(start_building) = controller.start_building {
-                        if start_building == building
-                            || !controller.placing_valid
-                            || q_building.get(start_building).unwrap().connected.contains(&building)
-                            || q_building.get(building).unwrap().connected.contains(&start_building)
-                        {
-                            continue;
-                        }
-                        ev_interact_way.send(InteractWay::Finish {
-                            from: start_building,
-                            connect_to: building,
-                        });
-                    } else {
-                        ev_interact_way.send(InteractWay::Start {
-                            from: building,
-                        });
-                    }
+                } if !placing_building
+                    && controller.start_building.is_none()
+                    && local_player.controls(&players, q_owners.get(building).ok()) =>
+                {
+                    ev_interact_way.send(InteractWay::Start {
+                        from: building,
+                    });
+                }
+                InputEvent::ClickedOnGround {
+                    position,
+                } if controller.start_building.is_some() && controller.path_clear => {
+                    controller.waypoints.push(position);
                 }
                 InputEvent::Abort => {
                     if let Some(start_building) = controller.start_building {
@@ -106,6 +483,30 @@ impl WayController {
             }
         }
     }
+
+    /// Stops placing a way once its start building is despawned or captured, or the match
+    /// restarts.
+    pub fn abort_with_building(
+        mut ev_despawn_building: EventReader<DespawnBuilding>,
+        mut ev_building_captured: EventReader<BuildingCaptured>,
+        mut ev_restart_match: EventReader<RestartMatch>,
+        mut ev_interact_way: EventWriter<InteractWay>,
+        controller: Res<WayController>,
+    ) {
+        let restarted = ev_restart_match.read().count() > 0;
+        let lost = ev_despawn_building
+            .read()
+            .map(|event| event.building)
+            .chain(ev_building_captured.read().map(|event| event.building))
+            .chain(controller.start_building.filter(|_| restarted));
+        for building in lost {
+            if controller.start_building == Some(building) {
+                ev_interact_way.send(InteractWay::Abort {
+                    aborted: building,
+                });
+            }
+        }
+    }
 }
 
 #[derive(Component)]
@@ -114,72 +515,58 @@ pub struct PlacingWay {
 }
 
 impl PlacingWay {
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         mut meshes: ResMut<Assets<Mesh>>,
-        mut query: Query<(
-            &PlacingWay,
-            &mut Handle<Mesh>,
-            &mut Handle<StandardMaterial>,
-            &Transform,
-        )>,
+        query: Query<(&PlacingWay, &Handle<Mesh>, &Handle<StandardMaterial>)>,
         q_buildings: Query<(&Building, &Transform)>,
+        q_ways: Query<&Way>,
         input_controller: Res<InputController>,
         mut way_controller: ResMut<WayController>,
-        spatial_query: SpatialQuery,
+        placement: Placement,
         mut materials: ResMut<Assets<StandardMaterial>>,
     ) {
-        for (way, mesh, material, transform) in query.iter_mut() {
-            let mesh = meshes.get_mut(mesh.id()).unwrap();
-            let vertex_positions = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION).unwrap();
-            let vertex_positions =
-                if let VertexAttributeValues::Float32x3(vertex_positions) = vertex_positions {
-                    vertex_positions
-                } else {
-                    return;
-                };
+        for (way, mesh, material) in query.iter() {
+            let Ok((from_building, from_transform)) = q_buildings.get(way.from) else {
+                continue;
+            };
 
-            let mut end_building = None;
+            let hovering = input_controller
+                .hovering_building
+                .and_then(|entity| Some((entity, q_buildings.get(entity).ok()?)));
 
-            let global_end_point =
-                if let Some(hovering_building) = input_controller.hovering_building {
-                    let (_, transform): (&Building, &Transform) =
-                        q_buildings.get(hovering_building).unwrap();
-                    end_building = Some(hovering_building);
-                    transform.translation
-                } else if let Some(plane_position) = input_controller.plane_position {
-                    plane_position
-                } else {
-                    return;
-                };
+            let global_end_point = if let Some((_, (_, transform))) = hovering {
+                transform.translation
+            } else if let Some(plane_position) = input_controller.plane_position {
+                plane_position
+            } else {
+                return;
+            };
 
-            let start_point = Vec3::ZERO;
-            let end_point = global_end_point - q_buildings.get(way.from).unwrap().1.translation;
-            let offset =
-                (end_point - start_point).try_normalize().unwrap_or(Vec3::X).cross(Vec3::Y) * 0.5;
-
-            vertex_positions[0] = (start_point + offset).to_array();
-            vertex_positions[1] = (start_point - offset).to_array();
-            vertex_positions[2] = (end_point - offset).to_array();
-            vertex_positions[3] = (end_point + offset).to_array();
-
-            let intersections = spatial_query.shape_intersections(
-                &Collider::trimesh_from_mesh(mesh).unwrap(),
-                transform.translation,
-                Quat::default(),
-                SpatialQueryFilter::default(),
+            let path = WayPath::curved(
+                from_transform.translation,
+                &way_controller.waypoints,
+                global_end_point,
             );
+            *meshes.get_mut(mesh.id()).unwrap() = path.ribbon_mesh(Way::WIDTH);
 
-            let material = materials.get_mut(material.id()).unwrap();
+            // only the buildings at both ends may touch the way, as the simulation checks once
+            // it is requested
+            let mut excluded = vec![way.from];
+            excluded.extend(hovering.map(|(entity, _)| entity));
+            let path_clear = placement.is_way_clear(&path, &excluded);
+            way_controller.path_clear = path_clear;
+            way_controller.placing_valid = path_clear
+                && hovering.is_some_and(|(end_building, (end, _))| {
+                    end_building != way.from
+                        && from_building.way_to(end_building, &q_ways).is_none()
+                        && end.way_to(way.from, &q_ways).is_none()
+                });
 
-            if end_building.is_some_and(|end_building| {
-                intersections.len() == 2
-                    && !q_buildings.get(way.from).unwrap().0.connected.contains(&end_building)
-                    && !q_buildings.get(end_building).unwrap().0.connected.contains(&way.from)
-            }) {
-                way_controller.placing_valid = true;
+            let material = materials.get_mut(material.id()).unwrap();
+            if way_controller.placing_valid || (hovering.is_none() && path_clear) {
                 material.base_color = Color::rgb(0.3, 0.5, 0.3);
             } else {
-                way_controller.placing_valid = false;
                 material.base_color = Color::rgb(0.8, 0.3, 0.3);
             }
         }
@@ -194,44 +581,150 @@ pub enum InteractWay {
     Finish {
         from: Entity,
         connect_to: Entity,
+        /// Ground points between the buildings the way bends through.
+        waypoints: Vec<Vec3>,
     },
     Abort {
         aborted: Entity,
     },
+    ToggleDirection {
+        way: Entity,
+    },
+    Remove {
+        way: Entity,
+    },
+    SetWeight {
+        way: Entity,
+        weight: u32,
+    },
 }
 
 impl InteractWay {
     const PLACEMENT_HEIGHT: f32 = 0.001;
 
     pub fn handle(
+        mut commands: Commands,
+        mut events: EventReader<InteractWay>,
+        mut q_buildings: Query<(&mut Building, &Transform)>,
+        mut q_ways: Query<&mut Way>,
+        mut q_units: Query<&mut Unit>,
+        q_owners: Query<&Owner>,
+    ) {
+        // ways spawned by this run are not visible to `q_ways` yet
+        let mut spawned_ways: Vec<Way> = Vec::new();
+
+        for event in events.read() {
+            match *event {
+                InteractWay::Finish {
+                    from,
+                    connect_to,
+                    ref waypoints,
+                } => {
+                    // fails for `from == connect_to` as well
+                    let Ok([(mut from_building, from_transform), (mut to_building, to_transform)]) =
+                        q_buildings.get_many_mut([from, connect_to])
+                    else {
+                        continue;
+                    };
+                    if from_building.way_to(connect_to, &q_ways.to_readonly()).is_some()
+                        || spawned_ways.iter().any(|way| way.connects(from, connect_to))
+                    {
+                        continue;
+                    }
+                    let path = WayPath::curved(
+                        from_transform.translation,
+                        waypoints,
+                        to_transform.translation,
+                    );
+                    let way = Way::new(from, connect_to, path);
+                    let mut entity = commands.spawn(way.clone());
+                    // ways belong to the owner of the building they start at
+                    if let Ok(owner) = q_owners.get(from) {
+                        entity.insert(*owner);
+                    }
+                    let entity = entity.id();
+                    spawned_ways.push(way);
+                    from_building.ways.push(entity);
+                    to_building.ways.push(entity);
+                }
+                InteractWay::ToggleDirection {
+                    way,
+                } => {
+                    if let Ok(mut way) = q_ways.get_mut(way) {
+                        way.direction = way.direction.toggled();
+                    }
+                }
+                InteractWay::SetWeight {
+                    way,
+                    weight,
+                } => {
+                    if let Ok(mut way) = q_ways.get_mut(way) {
+                        way.weight = weight.min(Way::MAX_WEIGHT);
+                    }
+                }
+                InteractWay::Remove {
+                    way: way_entity,
+                } => {
+                    let Ok(way) = q_ways.get(way_entity) else {
+                        continue;
+                    };
+                    for building in [way.from, way.to] {
+                        if let Ok((mut building, _)) = q_buildings.get_mut(building) {
+                            building.ways.retain(|way| *way != way_entity);
+                        }
+                    }
+                    // units still on the way return to where they came from
+                    for mut unit in q_units.iter_mut() {
+                        if way.connects(unit.from_building, unit.to_building) {
+                            unit.turn_around();
+                        }
+                    }
+                    commands.entity(way_entity).despawn_recursive();
+                }
+                InteractWay::Start {
+                    ..
+                }
+                | InteractWay::Abort {
+                    ..
+                } => {}
+            }
+        }
+    }
+
+    pub fn handle_placing(
         mut commands: Commands,
         mut events: EventReader<InteractWay>,
         mut controller: ResMut<WayController>,
         mut meshes: ResMut<Assets<Mesh>>,
         mut materials: ResMut<Assets<StandardMaterial>>,
-        mut q_ways: Query<(Entity, &mut Transform), With<PlacingWay>>,
-        mut q_buildings: Query<(&mut Building, &Transform), Without<PlacingWay>>,
+        q_ways: Query<Entity, With<PlacingWay>>,
+        q_buildings: Query<&Transform, (With<Building>, Without<PlacingWay>)>,
     ) {
         for event in events.read() {
             match *event {
                 InteractWay::Start {
                     from,
                 } => {
+                    let Ok(from_transform) = q_buildings.get(from) else {
+                        continue;
+                    };
                     controller.start_building = Some(from);
+                    controller.waypoints.clear();
 
-                    let base_mesh = meshes.get(controller.mesh.id()).unwrap().clone();
-                    let mesh = meshes.add(base_mesh);
+                    let mesh = meshes.add(
+                        WayPath::straight(from_transform.translation, from_transform.translation)
+                            .ribbon_mesh(Way::WIDTH),
+                    );
 
                     commands.spawn((
                         PlacingWay {
                             from,
                         },
                         PbrBundle {
+                            // the mesh is in world space
                             transform: Transform::from_translation(
-                                q_buildings.get(from).unwrap().1.translation
-                                    + Vec3::Y * Self::PLACEMENT_HEIGHT,
-                            )
-                            .with_rotation(Quat::from_rotation_x(0.0)),
+                                Vec3::Y * Self::PLACEMENT_HEIGHT,
+                            ),
                             mesh,
                             material: {
                                 let material =
@@ -242,25 +735,22 @@ impl InteractWay {
                         },
                     ));
                 }
+                // other players finish ways as well
                 InteractWay::Finish {
-                    connect_to: connected_to,
+                    from,
                     ..
-                } => {
-                    let (entity, mut transform) = q_ways.single_mut();
-                    let start_building = controller.start_building.take().unwrap();
-                    controller.connected.push((start_building, connected_to));
-                    let (mut building, _) = q_buildings.get_mut(start_building).unwrap();
-                    building.connected.push(connected_to);
-                    transform.translation.y -= Self::PLACEMENT_HEIGHT;
-                    commands.entity(entity).remove::<PlacingWay>();
                 }
-                InteractWay::Abort {
-                    aborted: _,
-                } => {
-                    let entity = q_ways.single().0;
-                    commands.entity(entity).despawn();
+                | InteractWay::Abort {
+                    aborted: from,
+                } if controller.start_building == Some(from) => {
+                    // finished ways get their own mesh once the simulation spawned them
+                    if let Ok(entity) = q_ways.get_single() {
+                        commands.entity(entity).despawn();
+                    }
                     controller.start_building = None;
+                    controller.waypoints.clear();
                 }
+                _ => {}
             }
         }
     }
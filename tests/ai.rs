@@ -0,0 +1,145 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    ai::{Ai, Difficulty},
+    building::headquarters::HeadQuarters,
+    economy::{Cargo, ResourceKind, Stockpile},
+    game::SimulationSettings,
+    map::MapSource,
+    map_generator::MapGenerator,
+    player::{Owner, Players},
+    unit::Unit,
+    unit_kind::UnitKind,
+    way::{Way, WayDirection},
+};
+
+fn way_between(harness: &mut Harness, building: Entity, other: Entity) -> Option<Way> {
+    harness
+        .building(building)
+        .ways
+        .iter()
+        .filter_map(|way| harness.app.world.get::<Way>(*way))
+        .find(|way| way.connects(building, other))
+        .cloned()
+}
+
+fn ai_vs_ai(difficulty: Difficulty) -> Harness {
+    Harness::with_settings(SimulationSettings {
+        map: Some(MapSource::Generated(MapGenerator::default())),
+        ai: BTreeMap::from([(0, difficulty), (1, difficulty)]),
+        ..default()
+    })
+}
+
+#[test]
+fn ai_harvests_the_closest_trees() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    harness.app.world.entity_mut(player).insert(Ai::new(Difficulty::Normal));
+    let head_quarters = harness.spawn_owned_building("head_quarters", Vec3::ZERO, Some(player));
+    let near = harness.spawn_tree(Vec3::new(4.0, 0.0, 0.0));
+    let middle = harness.spawn_tree(Vec3::new(0.0, 0.0, -6.0));
+    let far = harness.spawn_tree(Vec3::new(-10.0, 0.0, 0.0));
+    // hidden behind the near tree
+    let behind = harness.spawn_tree(Vec3::new(8.0, 0.0, 0.0));
+
+    harness.seconds(Difficulty::Normal.think_seconds() as f64 * 3.0);
+
+    assert!(way_between(&mut harness, head_quarters, near).is_some());
+    assert!(way_between(&mut harness, head_quarters, middle).is_some());
+    assert!(way_between(&mut harness, head_quarters, far).is_none());
+    assert!(way_between(&mut harness, head_quarters, behind).is_none());
+    let way = harness.building(head_quarters).ways[0];
+    assert_eq!(harness.app.world.get::<Owner>(way), Some(&Owner(player)));
+}
+
+#[test]
+fn ai_attacks_once_it_stored_enough_wood() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    let enemy = harness.add_player(1);
+    harness.app.world.entity_mut(player).insert(Ai::new(Difficulty::Hard));
+    let head_quarters = harness.spawn_owned_building("head_quarters", Vec3::ZERO, Some(player));
+    let enemy_head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, 8.0), Some(enemy));
+    let tree = harness.spawn_tree(Vec3::new(4.0, 0.0, 0.0));
+
+    harness.seconds(2.0);
+    assert_eq!(
+        harness.app.world.get::<HeadQuarters>(head_quarters).unwrap().unit_kind,
+        UnitKind::Worker
+    );
+    assert!(way_between(&mut harness, head_quarters, enemy_head_quarters).is_none());
+
+    harness.app.world.get_mut::<Stockpile>(head_quarters).unwrap().add(Cargo {
+        kind: ResourceKind::Wood,
+        amount: Difficulty::Hard.wood_per_wave(),
+    });
+    harness.seconds(Difficulty::Hard.think_seconds() as f64 * 6.0);
+
+    assert!(harness.app.world.get::<Ai>(player).unwrap().is_attacking());
+    assert_eq!(
+        harness.app.world.get::<HeadQuarters>(head_quarters).unwrap().unit_kind,
+        UnitKind::Soldier
+    );
+    let attack = way_between(&mut harness, head_quarters, enemy_head_quarters).unwrap();
+    assert_eq!(attack.weight, Way::MAX_WEIGHT);
+    assert_eq!(attack.direction, WayDirection::Forward);
+    assert_eq!(way_between(&mut harness, head_quarters, tree).unwrap().weight, 0);
+}
+
+#[test]
+fn ai_attacks_along_ways_built_from_the_enemy() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    let enemy = harness.add_player(1);
+    harness.app.world.entity_mut(player).insert(Ai::new(Difficulty::Hard));
+    let head_quarters = harness.spawn_owned_building("head_quarters", Vec3::ZERO, Some(player));
+    let enemy_head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, 8.0), Some(enemy));
+    // a way the enemy built towards the head quarters and the player captured
+    harness.connect(enemy_head_quarters, head_quarters);
+    let way = harness.building(head_quarters).ways[0];
+    harness.app.world.entity_mut(way).insert(Owner(player));
+
+    harness.app.world.get_mut::<Stockpile>(head_quarters).unwrap().add(Cargo {
+        kind: ResourceKind::Wood,
+        amount: Difficulty::Hard.wood_per_wave(),
+    });
+    harness.seconds(Difficulty::Hard.think_seconds() as f64 * 6.0);
+
+    let attack = harness.way(way);
+    assert_eq!(attack.weight, Way::MAX_WEIGHT);
+    assert_eq!(attack.direction, WayDirection::Backward);
+    assert_eq!(attack.destination_from(head_quarters), Some(enemy_head_quarters));
+}
+
+#[test]
+fn ais_play_each_other_the_same_way_every_time() {
+    let snapshot = |harness: &mut Harness| {
+        let mut q_units = harness.app.world.query::<(&Unit, &Transform, &Owner)>();
+        let mut units: Vec<_> = q_units
+            .iter(&harness.app.world)
+            .map(|(unit, transform, owner)| (unit.kind, transform.translation.to_array(), owner.0))
+            .collect();
+        units.sort_by(|a, b| format!("{a:?}").cmp(&format!("{b:?}")));
+        units
+    };
+
+    let mut first = ai_vs_ai(Difficulty::Hard);
+    first.seconds(60.0);
+    let mut second = ai_vs_ai(Difficulty::Hard);
+    second.seconds(60.0);
+
+    let players = first.app.world.resource::<Players>().0.clone();
+    let units = snapshot(&mut first);
+    for player in players {
+        assert!(first.app.world.get::<Ai>(player).is_some());
+        assert!(units.iter().any(|(.., owner)| *owner == player));
+    }
+    assert_eq!(units, snapshot(&mut second));
+}
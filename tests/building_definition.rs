@@ -0,0 +1,134 @@
+mod common;
+
+use bevy::{asset::LoadedFolder, prelude::*};
+use common::Harness;
+use flow_rts::{
+    building::{
+        definition::{BuildingDefinition, BuildingDefinitions},
+        headquarters::HeadQuarters,
+        tree::Tree,
+        Building, SpawnBuilding,
+    },
+    combat::{Control, Health},
+    economy::Stockpile,
+    unit_kind::UnitKind,
+};
+
+#[test]
+fn definitions_are_loaded_from_asset_folder() {
+    let harness = Harness::new();
+
+    let definitions = harness.app.world.resource::<BuildingDefinitions>();
+    let names: Vec<_> = definitions.names().collect();
+    assert_eq!(names, ["barracks", "head_quarters", "tree", "wood_storage"]);
+    let handle = definitions.get("tree").unwrap();
+    let tree = harness.app.world.resource::<Assets<BuildingDefinition>>().get(handle).unwrap();
+    assert_eq!(tree.scale, 2.0);
+    assert_eq!(tree.depleted_model(), Some("models/detail_tree.glb#Scene0"));
+}
+
+#[test]
+fn definitions_added_to_the_folder_are_registered() {
+    let mut harness = Harness::new();
+
+    // what the file watcher does for a new file: it loads it and then the folder again
+    let world = &mut harness.app.world;
+    let asset_server = world.resource::<AssetServer>().clone();
+    let castle = asset_server.load::<BuildingDefinition>("buildings/castle.building.ron");
+    let tree = world.resource::<BuildingDefinitions>().get("tree").unwrap().clone();
+    let mut definitions = world.resource_mut::<Assets<BuildingDefinition>>();
+    let definition = definitions.get(&tree).unwrap().clone();
+    definitions.insert(&castle, definition);
+    let folder = asset_server.get_handle::<LoadedFolder>(BuildingDefinitions::FOLDER).unwrap();
+    let mut folders = world.resource_mut::<Assets<LoadedFolder>>();
+    folders.get_mut(&folder).unwrap().handles.push(castle.clone().untyped());
+    harness.ticks(2);
+
+    let definitions = harness.app.world.resource::<BuildingDefinitions>();
+    let names: Vec<_> = definitions.names().collect();
+    assert_eq!(names, ["barracks", "castle", "head_quarters", "tree", "wood_storage"]);
+    assert_eq!(definitions.get("castle"), Some(&castle));
+}
+
+#[test]
+fn buildings_get_components_of_their_definition() {
+    let mut harness = Harness::new();
+    let barracks = harness.spawn_building("barracks", Vec3::new(0.0, 0.0, 5.0));
+    let storage = harness.spawn_building("wood_storage", Vec3::new(5.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(-5.0, 0.0, 0.0));
+
+    let world = &harness.app.world;
+    let head_quarters = world.get::<HeadQuarters>(barracks).unwrap();
+    assert_eq!(head_quarters.unit_kind, UnitKind::Soldier);
+    assert_eq!(head_quarters.spawn_timer.duration().as_secs_f32(), 8.0);
+    assert!(world.get::<Stockpile>(barracks).is_none());
+
+    assert!(world.get::<Stockpile>(storage).is_some());
+    assert!(world.get::<HeadQuarters>(storage).is_none());
+
+    assert_eq!(world.get::<Tree>(tree).unwrap().wood, 20);
+    assert_eq!(world.get::<Transform>(tree).unwrap().scale, Vec3::splat(2.0));
+}
+
+#[test]
+fn unknown_definitions_are_not_spawned() {
+    let mut harness = Harness::new();
+    harness.app.world.send_event(SpawnBuilding {
+        definition: "castle".to_owned(),
+        position: Vec3::ZERO,
+        owner: None,
+    });
+    harness.tick();
+
+    let mut q_buildings = harness.app.world.query::<&Building>();
+    assert_eq!(q_buildings.iter(&harness.app.world).count(), 0);
+}
+
+#[test]
+fn reloaded_definition_updates_spawned_buildings() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+
+    harness.edit_building_definition("head_quarters", |definition| {
+        definition.scale = 1.5;
+        definition.production.as_mut().unwrap().cooldown = 2.0;
+    });
+    harness.ticks(2);
+
+    let world = &harness.app.world;
+    assert_eq!(world.get::<Transform>(head_quarters).unwrap().scale, Vec3::splat(1.5));
+    let spawn_timer = &world.get::<HeadQuarters>(head_quarters).unwrap().spawn_timer;
+    assert_eq!(spawn_timer.duration().as_secs_f32(), 2.0);
+}
+
+#[test]
+fn reloaded_definition_adds_and_removes_components() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+
+    harness.edit_building_definition("head_quarters", |definition| {
+        definition.production = None;
+        definition.storage = false;
+        definition.health = Some(100);
+        definition.control = Some(4);
+    });
+    harness.ticks(2);
+
+    let world = &harness.app.world;
+    assert!(world.get::<HeadQuarters>(head_quarters).is_none());
+    assert!(world.get::<Stockpile>(head_quarters).is_none());
+    assert_eq!(
+        world.get::<Health>(head_quarters),
+        Some(&Health {
+            current: 100,
+            max: 100,
+        })
+    );
+    assert_eq!(
+        world.get::<Control>(head_quarters),
+        Some(&Control {
+            points: 4,
+            max: 4,
+        })
+    );
+}
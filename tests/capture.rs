@@ -0,0 +1,65 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{combat::Control, player::Owner};
+
+#[test]
+fn arriving_enemies_capture_buildings_and_their_ways() {
+    let mut harness = Harness::new();
+    let (attacker, defender) = (harness.add_player(0), harness.add_player(1));
+    let from = harness.spawn_owned_building("wood_storage", Vec3::ZERO, Some(attacker));
+    let target =
+        harness.spawn_owned_building("wood_storage", Vec3::new(4.0, 0.0, 0.0), Some(defender));
+    let depot =
+        harness.spawn_owned_building("wood_storage", Vec3::new(4.0, 0.0, -4.0), Some(defender));
+    let tree = harness.spawn_tree(Vec3::new(8.0, 0.0, 0.0));
+    harness.connect(depot, target);
+    harness.connect(target, tree);
+    harness.connect(from, target);
+    let [to_depot, to_tree, attack] = harness.building(target).ways[..] else {
+        panic!("expected three ways");
+    };
+    let points = harness.app.world.get::<Control>(target).unwrap().max;
+
+    for _ in 0..points - 1 {
+        harness.send_unit(from, target);
+    }
+    // they walk behind each other, half a second apart
+    harness.seconds(7.0);
+    assert_eq!(harness.app.world.get::<Owner>(target), Some(&Owner(defender)));
+    assert_eq!(harness.app.world.get::<Control>(target).unwrap().points, 1);
+
+    harness.send_unit(from, target);
+    harness.seconds(5.0);
+    assert_eq!(harness.app.world.get::<Owner>(target), Some(&Owner(attacker)));
+    assert_eq!(harness.app.world.get::<Control>(target).unwrap().points, points);
+    // the way to the defender's depot is gone, the others now belong to the attacker
+    assert_eq!(harness.building(target).ways, [to_tree, attack]);
+    assert!(harness.app.world.get_entity(to_depot).is_none());
+    assert_eq!(harness.app.world.get::<Owner>(to_tree), Some(&Owner(attacker)));
+    assert_eq!(harness.app.world.get::<Owner>(attack), Some(&Owner(attacker)));
+}
+
+#[test]
+fn harvesters_capture_neutral_trees() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    let head_quarters = harness.spawn_owned_building("head_quarters", Vec3::ZERO, Some(player));
+    let tree = harness.spawn_tree(Vec3::new(3.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    // workers leave every 5 seconds and take 3 seconds to get there
+    harness.seconds(14.0);
+    assert_eq!(harness.arrivals().iter().filter(|building| **building == tree).count(), 2);
+    assert!(harness.app.world.get::<Owner>(tree).is_none());
+
+    harness.seconds(5.0);
+    assert_eq!(harness.app.world.get::<Owner>(tree), Some(&Owner(player)));
+
+    // the player's own workers keep harvesting it without taking control points
+    harness.seconds(10.0);
+    assert_eq!(harness.app.world.get::<Owner>(tree), Some(&Owner(player)));
+    let control = harness.app.world.get::<Control>(tree).unwrap();
+    assert_eq!(control.points, control.max);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    combat::{Garrison, Health},
+    unit_kind::{UnitKind, UnitKinds},
+};
+
+/// Makes wood storages fragile, able to hold a garrison and destroyed instead of captured.
+fn weak_storages(harness: &mut Harness, garrison: u32) {
+    harness.edit_building_definition("wood_storage", |definition| {
+        definition.health = Some(10);
+        definition.control = None;
+        definition.garrison = garrison;
+    });
+}
+
+#[test]
+fn opposing_units_fight_until_they_die() {
+    let mut harness = Harness::new();
+    let (left, right) = (harness.add_player(0), harness.add_player(1));
+    let left_storage = harness.spawn_owned_building("wood_storage", Vec3::ZERO, Some(left));
+    let right_storage =
+        harness.spawn_owned_building("wood_storage", Vec3::new(8.0, 0.0, 0.0), Some(right));
+    harness.connect(left_storage, right_storage);
+
+    harness.send_unit(left_storage, right_storage);
+    harness.send_unit(right_storage, left_storage);
+    // they meet in the middle after about 3.5 seconds
+    harness.seconds(5.0);
+
+    let units = harness.units();
+    let health = harness.app.world.resource::<UnitKinds>().get(UnitKind::Worker).health;
+    assert_eq!(units.len(), 2);
+    assert!(units.iter().all(|unit| unit.fighting.is_some() && unit.health < health));
+    assert!(units[0].distance + units[1].distance <= 8.0);
+
+    // both deal 1 damage per round and fall at the same time
+    harness.seconds(10.0);
+    assert!(harness.units().is_empty());
+    assert!(harness.arrivals().is_empty());
+}
+
+#[test]
+fn allied_units_pass_each_other() {
+    let mut harness = Harness::new();
+    let (first, second) = (harness.add_player(0), harness.add_player(0));
+    let first_storage = harness.spawn_owned_building("wood_storage", Vec3::ZERO, Some(first));
+    let second_storage =
+        harness.spawn_owned_building("wood_storage", Vec3::new(8.0, 0.0, 0.0), Some(second));
+    harness.connect(first_storage, second_storage);
+
+    harness.send_unit(first_storage, second_storage);
+    harness.send_unit(second_storage, first_storage);
+    harness.seconds(9.0);
+
+    assert!(harness.arrivals().contains(&first_storage));
+    assert!(harness.arrivals().contains(&second_storage));
+}
+
+#[test]
+fn fighters_destroy_undefended_buildings() {
+    let mut harness = Harness::new();
+    weak_storages(&mut harness, 0);
+    let (attacker, defender) = (harness.add_player(0), harness.add_player(1));
+    let from = harness.spawn_owned_building("wood_storage", Vec3::ZERO, Some(attacker));
+    let target =
+        harness.spawn_owned_building("wood_storage", Vec3::new(4.0, 0.0, 0.0), Some(defender));
+    harness.connect(from, target);
+
+    // soldiers walk 4 at 0.8 per second and deal 5 damage each
+    harness.send_unit_of_kind(UnitKind::Soldier, from, target);
+    harness.seconds(5.5);
+    assert_eq!(
+        harness.app.world.get::<Health>(target),
+        Some(&Health {
+            current: 5,
+            max: 10,
+        })
+    );
+
+    harness.send_unit_of_kind(UnitKind::Soldier, from, target);
+    harness.seconds(5.5);
+    assert!(harness.app.world.get_entity(target).is_none());
+}
+
+#[test]
+fn garrison_is_joined_by_friends_and_defends_against_enemies() {
+    let mut harness = Harness::new();
+    weak_storages(&mut harness, 1);
+    let (attacker, defender) = (harness.add_player(0), harness.add_player(1));
+    let target =
+        harness.spawn_owned_building("wood_storage", Vec3::new(4.0, 0.0, 0.0), Some(defender));
+    let depot =
+        harness.spawn_owned_building("wood_storage", Vec3::new(4.0, 0.0, -4.0), Some(defender));
+    harness.connect(depot, target);
+    harness.send_unit_of_kind(UnitKind::Soldier, depot, target);
+    harness.seconds(5.5);
+    assert_eq!(harness.app.world.get::<Garrison>(target).unwrap().units, 1);
+
+    let from = harness.spawn_owned_building("wood_storage", Vec3::ZERO, Some(attacker));
+    harness.connect(from, target);
+    harness.send_unit_of_kind(UnitKind::Soldier, from, target);
+    harness.seconds(5.5);
+
+    assert_eq!(harness.app.world.get::<Garrison>(target).unwrap().units, 0);
+    assert_eq!(harness.app.world.get::<Health>(target).unwrap().current, 10);
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::headquarters::HeadQuarters,
+    command::{GameCommand, PlayerCommand},
+    economy::{Cargo, ResourceKind, Stockpile},
+    player::Owner,
+    unit_kind::UnitKind,
+    way::{Way, WayDirection},
+};
+
+#[test]
+fn players_only_command_what_they_own() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    let enemy = harness.add_player(1);
+    let head_quarters = harness.spawn_owned_building("head_quarters", Vec3::ZERO, Some(player));
+    let enemy_head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, 8.0), Some(enemy));
+    let tree = harness.spawn_tree(Vec3::new(4.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    let way = harness.building(head_quarters).ways[0];
+
+    harness.command(
+        1,
+        GameCommand::ConnectBuildings {
+            from: head_quarters,
+            to: enemy_head_quarters,
+            waypoints: Vec::new(),
+        },
+    );
+    harness.command(
+        1,
+        GameCommand::SetFlowWeight {
+            way,
+            weight: 3,
+        },
+    );
+    harness.command(
+        1,
+        GameCommand::ToggleWayDirection {
+            way,
+        },
+    );
+    harness.command(
+        1,
+        GameCommand::SelectUnitKind {
+            building: head_quarters,
+            kind: UnitKind::Soldier,
+        },
+    );
+    harness.command(
+        1,
+        GameCommand::RemoveWay {
+            way,
+        },
+    );
+
+    assert_eq!(harness.building(head_quarters).ways, [way]);
+    assert_eq!(harness.way(way).weight, Way::DEFAULT_WEIGHT);
+    assert_eq!(harness.way(way).direction, WayDirection::Both);
+    let unit_kind = harness.app.world.get::<HeadQuarters>(head_quarters).unwrap().unit_kind;
+    assert_eq!(unit_kind, UnitKind::Worker);
+
+    harness.command(
+        0,
+        GameCommand::SetFlowWeight {
+            way,
+            weight: 3,
+        },
+    );
+    harness.command(
+        0,
+        GameCommand::ToggleWayDirection {
+            way,
+        },
+    );
+    harness.command(
+        0,
+        GameCommand::SelectUnitKind {
+            building: head_quarters,
+            kind: UnitKind::Soldier,
+        },
+    );
+    assert_eq!(harness.way(way).weight, 3);
+    assert_eq!(harness.way(way).direction, WayDirection::Forward);
+    let unit_kind = harness.app.world.get::<HeadQuarters>(head_quarters).unwrap().unit_kind;
+    assert_eq!(unit_kind, UnitKind::Soldier);
+
+    // ways may lead to enemies, but only start at the player's own buildings
+    harness.command(
+        0,
+        GameCommand::ConnectBuildings {
+            from: head_quarters,
+            to: enemy_head_quarters,
+            waypoints: Vec::new(),
+        },
+    );
+    let attack = harness.building(enemy_head_quarters).ways[0];
+    assert_eq!(harness.app.world.get::<Owner>(attack), Some(&Owner(player)));
+    harness.command(
+        0,
+        GameCommand::RemoveWay {
+            way,
+        },
+    );
+    assert_eq!(harness.building(head_quarters).ways, [attack]);
+}
+
+#[test]
+fn ways_through_other_buildings_are_rejected() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::ZERO);
+    let tree = harness.spawn_tree(Vec3::new(8.0, 0.0, 0.0));
+    let barracks = harness.spawn_building("barracks", Vec3::new(4.0, 0.0, 0.0));
+    let connect = |waypoints| GameCommand::ConnectBuildings {
+        from: head_quarters,
+        to: tree,
+        waypoints,
+    };
+
+    harness.command(0, connect(Vec::new()));
+    assert!(harness.building(head_quarters).ways.is_empty());
+    assert!(harness.building(barracks).ways.is_empty());
+
+    // bending around it is fine
+    harness.command(0, connect(vec![Vec3::new(4.0, 0.0, 3.0)]));
+    assert_eq!(harness.building(head_quarters).ways.len(), 1);
+}
+
+#[test]
+fn flow_weights_above_the_maximum_are_rejected() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::ZERO);
+    let tree = harness.spawn_tree(Vec3::new(4.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    let way = harness.building(head_quarters).ways[0];
+
+    harness.command(
+        0,
+        GameCommand::SetFlowWeight {
+            way,
+            weight: Way::MAX_WEIGHT + 1,
+        },
+    );
+
+    assert_eq!(harness.way(way).weight, Way::DEFAULT_WEIGHT);
+}
+
+#[test]
+fn placed_buildings_belong_to_the_commanding_player() {
+    let mut harness = Harness::new();
+    let poor = harness.add_player(0);
+    let rich = harness.add_player(1);
+    harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, 5.0), Some(poor));
+    let head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, -5.0), Some(rich));
+    harness.app.world.get_mut::<Stockpile>(head_quarters).unwrap().add(Cargo {
+        kind: ResourceKind::Wood,
+        amount: 5,
+    });
+
+    harness.command(
+        1,
+        GameCommand::PlaceBuilding {
+            definition: "wood_storage".to_owned(),
+            position: Vec3::new(3.0, 0.0, 0.0),
+        },
+    );
+
+    let storage = harness.building_at(Vec3::new(3.0, 0.0, 0.0));
+    assert_eq!(harness.app.world.get::<Owner>(storage), Some(&Owner(rich)));
+}
+
+#[test]
+fn commands_survive_serialization() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::ZERO);
+    let tree = harness.spawn_tree(Vec3::new(4.0, 0.0, 0.0));
+    let commands = vec![
+        PlayerCommand {
+            player: 1,
+            command: GameCommand::ConnectBuildings {
+                from: head_quarters,
+                to: tree,
+                waypoints: vec![Vec3::new(2.0, 0.0, 1.5)],
+            },
+        },
+        PlayerCommand {
+            player: 0,
+            command: GameCommand::PlaceBuilding {
+                definition: "barracks".to_owned(),
+                position: Vec3::new(-3.0, 0.0, 2.0),
+            },
+        },
+        PlayerCommand {
+            player: 0,
+            command: GameCommand::SelectUnitKind {
+                building: head_quarters,
+                kind: UnitKind::Scout,
+            },
+        },
+    ];
+
+    let serialized = ron::to_string(&commands).unwrap();
+    let deserialized: Vec<PlayerCommand> = ron::from_str(&serialized).unwrap();
+
+    assert_eq!(deserialized, commands);
+}
@@ -0,0 +1,310 @@
+//! Runs the simulation headless, one fixed tick at a time.
+
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use flow_rts::{
+    building::{
+        construction::PlaceBuilding,
+        definition::{BuildingDefinition, BuildingDefinitions},
+        headquarters::SelectUnitKind,
+        Building, SpawnBuilding,
+    },
+    command::{GameCommand, PlayerCommand},
+    game::{
+        MatchState, RestartMatch, SimulationPlugin, SimulationSet, SimulationSettings,
+        SimulationTick,
+    },
+    headless::HeadlessPlugin,
+    map::MapSource,
+    player::{Player, Players, Team},
+    replay::{Replay, ReplayPlayback},
+    unit::{SpawnUnit, Unit, UnitArrived},
+    unit_kind::UnitKind,
+    way::{InteractWay, Way},
+};
+
+/// Collects every [`UnitArrived`] of the run, since events only live for two frames.
+#[derive(Resource, Default)]
+pub struct Arrivals(pub Vec<Entity>);
+
+fn record_arrivals(mut arrivals: ResMut<Arrivals>, mut ev_unit_arrived: EventReader<UnitArrived>) {
+    arrivals.0.extend(ev_unit_arrived.read().map(|event| event.building));
+}
+
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self::with_settings(SimulationSettings::default())
+    }
+
+    pub fn with_map(path: &str) -> Self {
+        Self::with_settings(SimulationSettings {
+            map: Some(MapSource::File(path.to_owned())),
+            ..default()
+        })
+    }
+
+    pub fn with_settings(settings: SimulationSettings) -> Self {
+        Self::with_setup(settings, |_| {})
+    }
+
+    /// Plays `replay` back from its first tick.
+    pub fn replaying(replay: Replay) -> Self {
+        Self::with_setup(replay.settings(), |app| {
+            app.insert_resource(ReplayPlayback::new(replay));
+        })
+    }
+
+    /// Lets `setup` change the app before the simulation starts.
+    pub fn with_setup(settings: SimulationSettings, setup: impl FnOnce(&mut App)) -> Self {
+        let mut app = App::new();
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / settings.tick_rate,
+        )))
+        .insert_resource(settings)
+        .add_plugins((HeadlessPlugin, SimulationPlugin))
+        .init_resource::<Arrivals>()
+        .add_systems(FixedUpdate, record_arrivals.after(SimulationSet::Update));
+        setup(&mut app);
+
+        let mut harness = Harness {
+            app,
+        };
+        harness.wait_for_start();
+        harness
+    }
+
+    /// Updates until the first tick ran, or the match waits in the lobby.
+    fn wait_for_start(&mut self) {
+        // assets load asynchronously, the simulation waits for them before its first tick
+        let start = Instant::now();
+        while self.app.world.get_resource::<SimulationTick>().map_or(0, |tick| tick.0) == 0
+            && self.app.world.get_resource::<State<MatchState>>().map(|state| *state.get())
+                != Some(MatchState::Lobby)
+        {
+            assert!(start.elapsed() < Duration::from_secs(10), "simulation did not start");
+            self.app.update();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    pub fn state(&self) -> MatchState {
+        *self.app.world.resource::<State<MatchState>>().get()
+    }
+
+    pub fn restart(&mut self) {
+        self.app.world.send_event(RestartMatch);
+        self.app.update();
+        self.wait_for_start();
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.app.world.resource::<SimulationTick>().0
+    }
+
+    /// Updates the app until exactly one more simulation tick has run.
+    pub fn tick(&mut self) {
+        let target = self.current_tick() + 1;
+        for _ in 0..10 {
+            self.app.update();
+            if self.current_tick() >= target {
+                assert_eq!(self.current_tick(), target, "ran more than one tick in a frame");
+                return;
+            }
+        }
+        panic!("simulation did not advance");
+    }
+
+    pub fn ticks(&mut self, count: u64) {
+        for _ in 0..count {
+            self.tick();
+        }
+    }
+
+    pub fn seconds(&mut self, seconds: f64) {
+        let tick_rate = self.app.world.resource::<SimulationSettings>().tick_rate;
+        self.ticks((seconds * tick_rate).ceil() as u64);
+    }
+
+    /// Adds a player, who is alone in its team unless another player has the same `team`.
+    pub fn add_player(&mut self, team: u32) -> Entity {
+        let existing = self
+            .app
+            .world
+            .query::<(Entity, &Team)>()
+            .iter(&self.app.world)
+            .find(|(_, other)| other.number == team)
+            .map(|(entity, _)| entity);
+        let team = existing.unwrap_or_else(|| {
+            self.app
+                .world
+                .spawn(Team {
+                    number: team,
+                })
+                .id()
+        });
+        let index = self.app.world.resource::<Players>().0.len();
+        let player = self.app.world.spawn(Player::new(index, team)).id();
+        self.app.world.resource_mut::<Players>().0.push(player);
+        player
+    }
+
+    pub fn spawn_building(&mut self, definition: &str, position: Vec3) -> Entity {
+        self.spawn_owned_building(definition, position, None)
+    }
+
+    pub fn spawn_owned_building(
+        &mut self,
+        definition: &str,
+        position: Vec3,
+        owner: Option<Entity>,
+    ) -> Entity {
+        self.app.world.send_event(SpawnBuilding {
+            definition: definition.to_owned(),
+            position,
+            owner,
+        });
+        self.tick();
+        self.building_at(position)
+    }
+
+    pub fn spawn_head_quarters(&mut self, position: Vec3) -> Entity {
+        self.spawn_building("head_quarters", position)
+    }
+
+    pub fn spawn_tree(&mut self, position: Vec3) -> Entity {
+        self.spawn_building("tree", position)
+    }
+
+    pub fn place_building(&mut self, definition: &str, position: Vec3) {
+        self.place_owned_building(definition, position, None);
+    }
+
+    pub fn place_owned_building(
+        &mut self,
+        definition: &str,
+        position: Vec3,
+        owner: Option<Entity>,
+    ) {
+        self.app.world.send_event(PlaceBuilding {
+            definition: definition.to_owned(),
+            position,
+            owner,
+        });
+        self.tick();
+    }
+
+    /// Changes a loaded definition the same way hot-reloading its file would.
+    pub fn edit_building_definition(
+        &mut self,
+        name: &str,
+        edit: impl FnOnce(&mut BuildingDefinition),
+    ) {
+        let handle = self.app.world.resource::<BuildingDefinitions>().get(name).unwrap().clone();
+        let mut definitions = self.app.world.resource_mut::<Assets<BuildingDefinition>>();
+        edit(definitions.get_mut(handle).unwrap());
+    }
+
+    pub fn building_at(&mut self, position: Vec3) -> Entity {
+        self.app
+            .world
+            .query_filtered::<(Entity, &Transform), With<Building>>()
+            .iter(&self.app.world)
+            .find(|(_, transform)| transform.translation == position)
+            .map(|(entity, _)| entity)
+            .expect("no building at position")
+    }
+
+    pub fn connect(&mut self, from: Entity, connect_to: Entity) {
+        self.connect_via(from, connect_to, Vec::new());
+    }
+
+    pub fn connect_via(&mut self, from: Entity, connect_to: Entity, waypoints: Vec<Vec3>) {
+        self.app.world.send_event(InteractWay::Start {
+            from,
+        });
+        self.app.world.send_event(InteractWay::Finish {
+            from,
+            connect_to,
+            waypoints,
+        });
+        self.tick();
+    }
+
+    pub fn toggle_direction(&mut self, way: Entity) {
+        self.app.world.send_event(InteractWay::ToggleDirection {
+            way,
+        });
+        self.tick();
+    }
+
+    pub fn set_weight(&mut self, way: Entity, weight: u32) {
+        self.app.world.send_event(InteractWay::SetWeight {
+            way,
+            weight,
+        });
+        self.tick();
+    }
+
+    pub fn remove_way(&mut self, way: Entity) {
+        self.app.world.send_event(InteractWay::Remove {
+            way,
+        });
+        self.tick();
+    }
+
+    /// Issues a command for the player at `player` in [`Players`].
+    pub fn command(&mut self, player: usize, command: GameCommand) {
+        self.app.world.send_event(PlayerCommand {
+            player,
+            command,
+        });
+        self.tick();
+    }
+
+    /// Sends an empty worker that belongs to `from`.
+    pub fn send_unit(&mut self, from: Entity, destination: Entity) {
+        self.send_unit_of_kind(UnitKind::Worker, from, destination);
+    }
+
+    pub fn send_unit_of_kind(&mut self, kind: UnitKind, from: Entity, destination: Entity) {
+        self.app.world.send_event(SpawnUnit {
+            kind,
+            from_building: from,
+            destination,
+            home: from,
+            cargo: None,
+        });
+        self.tick();
+    }
+
+    pub fn select_unit_kind(&mut self, building: Entity, kind: UnitKind) {
+        self.app.world.send_event(SelectUnitKind {
+            building,
+            kind,
+        });
+        self.tick();
+    }
+
+    pub fn way(&self, entity: Entity) -> &Way {
+        self.app.world.get::<Way>(entity).unwrap()
+    }
+
+    pub fn building(&self, entity: Entity) -> &Building {
+        self.app.world.get::<Building>(entity).unwrap()
+    }
+
+    pub fn units(&mut self) -> Vec<Unit> {
+        self.app.world.query::<&Unit>().iter(&self.app.world).cloned().collect()
+    }
+
+    pub fn arrivals(&self) -> &[Entity] {
+        &self.app.world.resource::<Arrivals>().0
+    }
+}
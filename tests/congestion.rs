@@ -0,0 +1,103 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::headquarters::HeadQuarters,
+    unit::{SpawnUnit, WaitingUnits},
+    unit_kind::UnitKind,
+    way::Way,
+};
+
+/// Distances of the units walking from `from` to the building they head to, front first.
+fn queue(harness: &mut Harness, from: Entity) -> Vec<f32> {
+    let mut distances: Vec<_> = harness
+        .units()
+        .into_iter()
+        .filter(|unit| unit.from_building == from)
+        .map(|unit| unit.distance)
+        .collect();
+    distances.sort_by(|a, b| b.total_cmp(a));
+    distances
+}
+
+#[test]
+fn units_keep_their_distance() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(10.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    for _ in 0..4 {
+        harness.send_unit(head_quarters, tree);
+    }
+    harness.seconds(3.0);
+
+    let distances = queue(&mut harness, head_quarters);
+    assert_eq!(distances.len(), 4);
+    assert!(distances.windows(2).all(|pair| pair[0] - pair[1] >= Way::UNIT_SPACING - 1e-4));
+    // the first unit walked freely
+    assert!((distances[0] - 3.0).abs() < 0.1);
+}
+
+#[test]
+fn full_ways_pause_the_head_quarters() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(1.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    let way = harness.building(head_quarters).ways[0];
+    assert_eq!(harness.way(way).capacity(), 2);
+
+    for _ in 0..6 {
+        harness.send_unit(head_quarters, tree);
+    }
+    let elapsed = |harness: &Harness| {
+        harness.app.world.get::<HeadQuarters>(head_quarters).unwrap().spawn_timer.elapsed_secs()
+    };
+    let waiting =
+        |harness: &Harness| harness.app.world.resource::<WaitingUnits>().at(head_quarters);
+    let paused_at = elapsed(&harness);
+
+    // units queue up at the head quarters and slowly leave
+    harness.seconds(1.0);
+    assert_eq!(elapsed(&harness), paused_at);
+    assert!(waiting(&harness) > 0);
+
+    // the timer continues once the queue is gone
+    harness.seconds(8.0);
+    assert_eq!(waiting(&harness), 0);
+    assert!(harness.units().iter().all(|unit| unit.from_building != head_quarters));
+    assert!(elapsed(&harness) > paused_at);
+}
+
+#[test]
+fn ways_never_hold_more_units_than_their_capacity() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(1.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    let way = harness.building(head_quarters).ways[0];
+    let capacity = harness.way(way).capacity();
+
+    // all requested in the same tick
+    for _ in 0..6 {
+        harness.app.world.send_event(SpawnUnit {
+            kind: UnitKind::Soldier,
+            from_building: head_quarters,
+            destination: tree,
+            home: head_quarters,
+            cargo: None,
+        });
+    }
+    let mut most = 0;
+    for _ in 0..600 {
+        harness.tick();
+        // the soldiers come back on the other lane
+        let on_way = harness.units().iter().filter(|unit| unit.lane() == (way, false)).count();
+        assert!(on_way <= capacity, "{on_way} units on a way for {capacity}");
+        most = most.max(on_way);
+    }
+    assert_eq!(most, capacity);
+    assert_eq!(harness.arrivals().iter().filter(|building| **building == tree).count(), 6);
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::{construction::UnderConstruction, Building},
+    economy::{Cargo, ResourceKind, Stockpile},
+};
+
+fn store_wood(harness: &mut Harness, building: Entity, amount: u32) {
+    harness.app.world.get_mut::<Stockpile>(building).unwrap().add(Cargo {
+        kind: ResourceKind::Wood,
+        amount,
+    });
+}
+
+fn building_count(harness: &mut Harness) -> usize {
+    harness.app.world.query::<&Building>().iter(&harness.app.world).count()
+}
+
+#[test]
+fn placing_takes_cost_from_stockpiles() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    store_wood(&mut harness, head_quarters, 7);
+
+    harness.place_building("wood_storage", Vec3::ZERO);
+
+    let site = harness.building_at(Vec3::ZERO);
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 2);
+    assert_eq!(
+        harness.app.world.get::<UnderConstruction>(site),
+        Some(&UnderConstruction {
+            remaining: 2,
+            work: 2,
+        })
+    );
+}
+
+#[test]
+fn unaffordable_or_overlapping_buildings_are_not_placed() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    store_wood(&mut harness, head_quarters, 4);
+
+    harness.place_building("wood_storage", Vec3::ZERO);
+    assert_eq!(building_count(&mut harness), 1);
+
+    store_wood(&mut harness, head_quarters, 100);
+    harness.place_building("wood_storage", Vec3::new(0.5, 0.0, 4.5));
+    // trees can only be part of a map
+    harness.place_building("tree", Vec3::ZERO);
+    assert_eq!(building_count(&mut harness), 1);
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 104);
+}
+
+#[test]
+fn buildings_are_not_placed_over_ways() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(0.0, 0.0, -5.0));
+    harness.connect(head_quarters, tree);
+    store_wood(&mut harness, head_quarters, 10);
+
+    harness.place_building("wood_storage", Vec3::ZERO);
+    assert_eq!(building_count(&mut harness), 2);
+
+    // right next to the way is fine
+    harness.place_building("wood_storage", Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(building_count(&mut harness), 3);
+}
+
+#[test]
+fn arriving_units_finish_construction() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    store_wood(&mut harness, head_quarters, 20);
+    harness.place_building("barracks", Vec3::ZERO);
+    let barracks = harness.building_at(Vec3::ZERO);
+    harness.connect(head_quarters, barracks);
+    let way = harness.building(head_quarters).ways[0];
+    harness.toggle_direction(way);
+
+    // a unit leaves every 5 seconds, once the one before cleared the entrance of the way, and
+    // takes another 5 seconds to arrive
+    harness.seconds(21.5);
+    assert_eq!(harness.arrivals(), [barracks, barracks, barracks]);
+    assert!(harness.app.world.get::<UnderConstruction>(barracks).is_none());
+
+    // the barracks only produces once it is finished, sending soldiers back
+    harness.toggle_direction(way);
+    harness.toggle_direction(way);
+    harness.seconds(8.5);
+    assert!(harness.units().iter().any(|unit| unit.from_building == barracks));
+}
+
+#[test]
+fn enemy_units_do_not_finish_construction() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    let enemy = harness.add_player(1);
+    let head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, 5.0), Some(player));
+    let enemy_head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, -5.0), Some(enemy));
+    store_wood(&mut harness, head_quarters, 5);
+    harness.place_owned_building("wood_storage", Vec3::ZERO, Some(player));
+    let site = harness.building_at(Vec3::ZERO);
+    harness.connect(enemy_head_quarters, site);
+
+    for _ in 0..2 {
+        harness.send_unit(enemy_head_quarters, site);
+    }
+    harness.seconds(8.0);
+
+    assert_eq!(harness.arrivals(), [site, site]);
+    assert_eq!(
+        harness.app.world.get::<UnderConstruction>(site),
+        Some(&UnderConstruction {
+            remaining: 2,
+            work: 2,
+        })
+    );
+}
+
+#[test]
+fn unfinished_buildings_produce_nothing() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    store_wood(&mut harness, head_quarters, 20);
+    harness.place_building("barracks", Vec3::ZERO);
+    let barracks = harness.building_at(Vec3::ZERO);
+    harness.connect(barracks, head_quarters);
+    let way = harness.building(head_quarters).ways[0];
+    // only from the barracks to the head quarters
+    harness.toggle_direction(way);
+
+    harness.seconds(20.0);
+    assert!(harness.units().is_empty());
+    assert!(harness.arrivals().is_empty());
+}
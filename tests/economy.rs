@@ -0,0 +1,74 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::definition::Behaviour,
+    economy::{ResourceKind, Stockpile},
+};
+
+#[test]
+fn units_deliver_wood_from_tree_to_head_quarters() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    // leaves after 5 seconds, about 7.1 seconds to the tree and the same way back
+    harness.seconds(19.0);
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 0);
+
+    harness.seconds(0.5);
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 1);
+    assert_eq!(harness.arrivals(), [tree, tree, head_quarters]);
+}
+
+#[test]
+fn depleted_tree_is_despawned_and_disconnected() {
+    let mut harness = Harness::new();
+    harness.edit_building_definition("tree", |definition| {
+        let Behaviour::Harvestable {
+            wood,
+            ..
+        } = &mut definition.behaviours[0];
+        *wood = 1;
+    });
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    // the first unit harvests the only wood, the second one is already on its way
+    harness.seconds(12.5);
+    assert!(harness.app.world.get_entity(tree).is_none());
+    assert!(harness.building(head_quarters).ways.is_empty());
+
+    // it returns home without cargo
+    harness.seconds(10.0);
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 1);
+    assert!(harness.units().is_empty());
+    assert_eq!(harness.arrivals(), [tree, head_quarters, head_quarters]);
+}
+
+#[test]
+fn units_return_empty_from_trees_without_wood() {
+    let mut harness = Harness::new();
+    harness.edit_building_definition("tree", |definition| {
+        let Behaviour::Harvestable {
+            wood,
+            ..
+        } = &mut definition.behaviours[0];
+        *wood = 0;
+    });
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    // leaves after 5 seconds, about 7.1 seconds to the tree and the same way back
+    harness.seconds(20.0);
+    assert_eq!(harness.arrivals(), [tree, tree, head_quarters]);
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 0);
+}
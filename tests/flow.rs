@@ -0,0 +1,73 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::way::FlowDistributor;
+
+#[test]
+fn distributor_interleaves_by_weight() {
+    let [a, b, c] = [Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3)];
+    let mut distributor = FlowDistributor::default();
+
+    let picks: Vec<_> =
+        (0..7).map(|_| distributor.next(&[(a, 5), (b, 1), (c, 1)], |_| true).unwrap()).collect();
+
+    assert_eq!(picks, [a, a, b, a, c, a, a]);
+}
+
+#[test]
+fn distributor_skips_zero_weights() {
+    let [a, b] = [Entity::from_raw(1), Entity::from_raw(2)];
+    let mut distributor = FlowDistributor::default();
+
+    assert_eq!(distributor.next(&[(a, 0), (b, 0)], |_| true), None);
+    assert!((0..4).all(|_| distributor.next(&[(a, 0), (b, 2)], |_| true) == Some(b)));
+}
+
+#[test]
+fn distributor_keeps_the_share_of_ways_closed_for_a_while() {
+    let [a, b, c] = [Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3)];
+    let ways = [(a, 1), (b, 1), (c, 2)];
+    let mut distributor = FlowDistributor::default();
+
+    let mut picks: Vec<_> = (0..2).map(|_| distributor.next(&ways, |_| true).unwrap()).collect();
+    for _ in 0..2 {
+        picks.push(distributor.next(&ways, |way| way != b).unwrap());
+    }
+    picks.extend((0..40).map(|_| distributor.next(&ways, |_| true).unwrap()));
+
+    let count = |way: Entity| picks.iter().filter(|pick| **pick == way).count();
+    assert_eq!([count(a), count(b), count(c)], [11, 11, 22]);
+}
+
+#[test]
+fn head_quarters_sends_units_by_way_weight() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(100.0, 0.0, 0.0));
+    let other_tree = harness.spawn_tree(Vec3::new(-100.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    harness.connect(head_quarters, other_tree);
+    let way = harness.building(head_quarters).ways[0];
+    harness.set_weight(way, 3);
+
+    // a unit every 5 seconds, none of them arrives yet
+    harness.seconds(40.5);
+
+    let destinations: Vec<_> = harness.units().iter().map(|unit| unit.to_building).collect();
+    assert_eq!(destinations.len(), 8);
+    assert_eq!(destinations.iter().filter(|destination| **destination == tree).count(), 6);
+}
+
+#[test]
+fn ways_with_zero_weight_get_no_units() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    let way = harness.building(head_quarters).ways[0];
+    harness.set_weight(way, 0);
+
+    harness.seconds(6.0);
+    assert!(harness.units().is_empty());
+}
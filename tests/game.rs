@@ -0,0 +1,167 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::{Building, DespawnBuilding},
+    economy::{Cargo, ResourceKind, Stockpile},
+    game::{Game, MatchState, SimulationSettings},
+    map::{CurrentMap, Map, MapSource, WinCondition},
+    map_generator::MapGenerator,
+    player::{Owner, Player, Players},
+};
+
+fn player(harness: &Harness, index: usize) -> Player {
+    let entity = harness.app.world.resource::<Players>().get(index).unwrap();
+    harness.app.world.get::<Player>(entity).unwrap().clone()
+}
+
+fn building_count(harness: &mut Harness) -> usize {
+    harness.app.world.query::<&Building>().iter(&harness.app.world).count()
+}
+
+fn set_win_conditions(harness: &mut Harness, win_conditions: Vec<WinCondition>) {
+    let handle = harness.app.world.resource::<CurrentMap>().handle.clone().unwrap();
+    let mut maps = harness.app.world.resource_mut::<Assets<Map>>();
+    maps.get_mut(handle).unwrap().win_conditions = win_conditions;
+}
+
+/// Keeps updating the app for a while, as the simulation stops advancing once the match is over.
+fn finish_match(harness: &mut Harness, seconds: f64) {
+    let tick_rate = harness.app.world.resource::<SimulationSettings>().tick_rate;
+    for _ in 0..(seconds * tick_rate).ceil() as u64 {
+        harness.app.update();
+    }
+}
+
+#[test]
+fn match_starts_after_loading_or_waits_in_lobby() {
+    let harness = Harness::new();
+    assert_eq!(harness.state(), MatchState::Playing);
+
+    let mut harness = Harness::with_settings(SimulationSettings {
+        lobby: true,
+        ..default()
+    });
+    assert_eq!(harness.state(), MatchState::Lobby);
+    harness.app.world.resource_mut::<NextState<MatchState>>().set(MatchState::Playing);
+    harness.tick();
+    assert_eq!(harness.state(), MatchState::Playing);
+}
+
+#[test]
+fn reaching_resource_target_wins() {
+    let mut harness = Harness::with_map("maps/default.map.ron");
+    let head_quarters = harness.building_at(Vec3::new(0.0, 0.0, 5.0));
+    harness.app.world.get_mut::<Stockpile>(head_quarters).unwrap().add(Cargo {
+        kind: ResourceKind::Wood,
+        amount: 99,
+    });
+    harness.tick();
+    assert_eq!(harness.state(), MatchState::Playing);
+
+    harness.app.world.get_mut::<Stockpile>(head_quarters).unwrap().add(Cargo {
+        kind: ResourceKind::Wood,
+        amount: 1,
+    });
+    harness.tick();
+    harness.app.update();
+    assert_eq!(harness.state(), MatchState::GameOver);
+    assert_eq!(harness.app.world.resource::<Game>().winner, Some(player(&harness, 0).team));
+}
+
+#[test]
+fn destroying_all_enemy_head_quarters_wins() {
+    let mut harness = Harness::with_settings(SimulationSettings {
+        map: Some(MapSource::Generated(MapGenerator::default())),
+        ..default()
+    });
+    let loser = harness.app.world.resource::<Players>().get(1).unwrap();
+    let mut q_buildings = harness.app.world.query::<(Entity, &Owner)>();
+    let head_quarters = q_buildings
+        .iter(&harness.app.world)
+        .find(|(_, owner)| owner.0 == loser)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    harness.seconds(1.0);
+    assert_eq!(harness.state(), MatchState::Playing);
+
+    harness.app.world.send_event(DespawnBuilding {
+        building: head_quarters,
+    });
+    finish_match(&mut harness, 0.5);
+    assert_eq!(harness.state(), MatchState::GameOver);
+    assert_eq!(harness.app.world.resource::<Game>().winner, Some(player(&harness, 0).team));
+}
+
+#[test]
+fn losing_the_last_head_quarters_at_once_is_a_draw() {
+    let mut harness = Harness::with_settings(SimulationSettings {
+        map: Some(MapSource::Generated(MapGenerator::default())),
+        ..default()
+    });
+    let players = harness.app.world.resource::<Players>().0.clone();
+    let mut q_buildings = harness.app.world.query::<(Entity, &Owner)>();
+    let head_quarters: Vec<_> = q_buildings
+        .iter(&harness.app.world)
+        .filter(|(_, owner)| players.contains(&owner.0))
+        .map(|(entity, _)| entity)
+        .collect();
+    assert_eq!(head_quarters.len(), 2);
+    harness.seconds(1.0);
+
+    for building in head_quarters {
+        harness.app.world.send_event(DespawnBuilding {
+            building,
+        });
+    }
+    finish_match(&mut harness, 0.5);
+    assert_eq!(harness.state(), MatchState::GameOver);
+    let game = harness.app.world.resource::<Game>();
+    assert!(game.draw);
+    assert_eq!(game.winner, None);
+}
+
+#[test]
+fn holding_buildings_long_enough_wins() {
+    let mut harness = Harness::with_map("maps/default.map.ron");
+    set_win_conditions(
+        &mut harness,
+        vec![WinCondition::HoldBuildings {
+            count: 2,
+            seconds: 3.0,
+        }],
+    );
+    let owner = harness.app.world.resource::<Players>().get(0);
+    harness.spawn_owned_building("wood_storage", Vec3::new(3.0, 0.0, 3.0), owner);
+
+    harness.seconds(2.5);
+    assert_eq!(harness.state(), MatchState::Playing);
+    finish_match(&mut harness, 1.0);
+    assert_eq!(harness.state(), MatchState::GameOver);
+}
+
+#[test]
+fn restarting_spawns_the_map_again() {
+    let mut harness = Harness::with_map("maps/default.map.ron");
+    let initial_buildings = building_count(&mut harness);
+    let owner = harness.app.world.resource::<Players>().get(0);
+    harness.spawn_owned_building("wood_storage", Vec3::new(3.0, 0.0, 3.0), owner);
+    let head_quarters = harness.building_at(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.building_at(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    harness.seconds(6.0);
+    assert!(!harness.units().is_empty());
+
+    harness.restart();
+
+    assert_eq!(harness.state(), MatchState::Playing);
+    assert_eq!(harness.current_tick(), 1);
+    assert_eq!(building_count(&mut harness), initial_buildings);
+    assert!(harness.units().is_empty());
+    assert_eq!(harness.app.world.resource::<Players>().0.len(), 1);
+    let mut q_players = harness.app.world.query::<&Player>();
+    assert_eq!(q_players.iter(&harness.app.world).count(), 1);
+    let head_quarters = harness.building_at(Vec3::new(0.0, 0.0, 5.0));
+    assert!(harness.building(head_quarters).ways.is_empty());
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+
+#[test]
+fn spawns_nothing_without_connections() {
+    let mut harness = Harness::new();
+    harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+
+    harness.seconds(12.0);
+
+    assert!(harness.units().is_empty());
+}
+
+#[test]
+fn distributes_units_round_robin() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let trees = [
+        harness.spawn_tree(Vec3::new(-9.0, 0.0, -14.0)),
+        harness.spawn_tree(Vec3::new(-6.0, 0.0, -8.0)),
+    ];
+    harness.connect(head_quarters, trees[0]);
+    harness.connect(head_quarters, trees[1]);
+
+    // units take more than 15 seconds to reach either tree, so all of them are still in flight
+    harness.seconds(15.5);
+
+    let units = harness.units();
+    assert_eq!(units.len(), 3);
+    assert!(units.iter().all(|unit| unit.from_building == head_quarters));
+    let mut targets: Vec<_> = units.iter().map(|unit| unit.to_building).collect();
+    targets.sort();
+    let mut expected = vec![trees[0], trees[0], trees[1]];
+    expected.sort();
+    assert_eq!(targets, expected);
+}
+
+#[test]
+fn units_arrive_at_connected_building() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    // the first unit leaves after 5 seconds and needs about 7.1 seconds for the way
+    harness.seconds(12.0);
+    assert!(harness.arrivals().is_empty());
+    harness.seconds(0.5);
+
+    assert_eq!(harness.arrivals(), [tree]);
+    // the second unit is on its way, the first one returns with wood
+    let units = harness.units();
+    assert_eq!(units.len(), 2);
+    assert_eq!(units.iter().filter(|unit| unit.cargo.is_some()).count(), 1);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::{headquarters::HeadQuarters, tree::Tree, Building},
+    economy::ResourceKind,
+    map::{CurrentMap, Map, WinCondition},
+};
+
+#[test]
+fn map_buildings_are_spawned_in_first_tick() {
+    let mut harness = Harness::with_map("maps/default.map.ron");
+
+    let head_quarters = harness.building_at(Vec3::new(0.0, 0.0, 5.0));
+    assert!(harness.app.world.get::<HeadQuarters>(head_quarters).is_some());
+    let mut q_trees = harness.app.world.query::<&Tree>();
+    assert_eq!(q_trees.iter(&harness.app.world).count(), 3);
+
+    // nothing is spawned twice
+    harness.seconds(1.0);
+    assert_eq!(q_trees.iter(&harness.app.world).count(), 3);
+}
+
+#[test]
+fn map_describes_players_and_win_conditions() {
+    let harness = Harness::with_map("maps/default.map.ron");
+
+    let handle = harness.app.world.resource::<CurrentMap>().handle.clone().unwrap();
+    let map = harness.app.world.resource::<Assets<Map>>().get(handle).unwrap();
+    assert_eq!(map.players.len(), 1);
+    assert_eq!(map.buildings().count(), 4);
+    assert_eq!(
+        map.win_conditions,
+        [WinCondition::Resources {
+            kind: ResourceKind::Wood,
+            amount: 100,
+        }]
+    );
+}
+
+#[test]
+fn without_map_world_starts_empty() {
+    let mut harness = Harness::new();
+
+    let mut q_buildings = harness.app.world.query::<&Building>();
+    assert_eq!(q_buildings.iter(&harness.app.world).count(), 0);
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::Building,
+    game::SimulationSettings,
+    map::MapSource,
+    map_generator::{Biome, MapGenerator},
+    terrain::Terrain,
+};
+
+#[test]
+fn same_seed_gives_same_map() {
+    let generator = MapGenerator {
+        seed: 7,
+        ..default()
+    };
+    assert_eq!(generator.generate(), generator.clone().generate());
+
+    let other = MapGenerator {
+        seed: 8,
+        ..default()
+    };
+    assert_ne!(generator.generate().neutral, other.generate().neutral);
+}
+
+#[test]
+fn two_player_maps_are_point_symmetric() {
+    let map = MapGenerator {
+        seed: 3,
+        river_count: 2,
+        ..default()
+    }
+    .generate();
+
+    assert_eq!(map.players.len(), 2);
+    let (x, z) = map.players[0].buildings[0].position;
+    assert_eq!(map.players[1].buildings[0].position, (-x, -z));
+    assert!(!map.neutral.is_empty());
+    for tree in &map.neutral {
+        let (x, z) = tree.position;
+        assert!(map.neutral.iter().any(|other| other.position == (-x, -z)));
+    }
+    for tile in &map.tiles {
+        let (x, z) = tile.position;
+        let mirrored = map.tiles.iter().find(|other| other.position == (-x, -z)).unwrap();
+        assert_eq!(mirrored.model, tile.model);
+        assert_eq!(mirrored.rotation, (tile.rotation + 2) % 4);
+    }
+}
+
+#[test]
+fn four_player_maps_are_rotationally_symmetric() {
+    let map = MapGenerator {
+        seed: 11,
+        player_count: 4,
+        size: 24,
+        ..default()
+    }
+    .generate();
+
+    assert_eq!(map.players.len(), 4);
+    for tree in &map.neutral {
+        let (x, z) = tree.position;
+        assert!(map.neutral.iter().any(|other| other.position == (z, -x)));
+    }
+}
+
+#[test]
+fn generated_maps_are_decorated_with_details() {
+    let map = MapGenerator {
+        seed: 4,
+        ..default()
+    }
+    .generate();
+
+    assert!(!map.details.is_empty());
+    for detail in &map.details {
+        assert!(detail.model.starts_with("detail_"));
+        let (x, z) = detail.position;
+        let mirrored = map.details.iter().find(|other| other.position == (-x, -z)).unwrap();
+        assert_eq!(mirrored.model, detail.model);
+        assert_eq!(mirrored.rotation, (detail.rotation + 2) % 4);
+        // only on free ground
+        assert!(map.tiles.iter().all(|tile| tile.position != detail.position));
+        let position = (x as f32, z as f32);
+        assert!(map.buildings().all(|building| building.position != position));
+    }
+}
+
+#[test]
+fn snow_biome_uses_snow_tiles() {
+    let map = MapGenerator {
+        biome: Biome::Snow,
+        ..default()
+    }
+    .generate();
+
+    assert_eq!(map.ground.unwrap().model, "snow_tile");
+    assert!(map.tiles.iter().all(|tile| tile.model.starts_with("snow_tile_")));
+    assert!(map.details.iter().all(|detail| detail.model.starts_with("snow_detail_")));
+}
+
+#[test]
+fn start_areas_stay_clear_on_small_maps() {
+    for seed in 0..20 {
+        let map = MapGenerator {
+            seed,
+            size: MapGenerator::MIN_SIZE,
+            player_count: 4,
+            river_count: 4,
+            ..default()
+        }
+        .generate();
+        let terrain = Terrain::from_map(&map);
+
+        for player in &map.players {
+            let start = Terrain::cell(player.buildings[0].translation());
+            // the clearance around the head quarters
+            for x in -2..=2 {
+                for z in -2..=2 {
+                    let position = Terrain::cell_center((start.0 + x, start.1 + z));
+                    assert!(
+                        terrain.is_walkable(position) && terrain.is_buildable(position),
+                        "seed {seed} blocks {position} next to the start at {start:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn generated_map_can_be_played() {
+    let generator = MapGenerator {
+        seed: 5,
+        player_count: 3,
+        river_count: 3,
+        ..default()
+    };
+    let map = generator.generate();
+    let mut harness = Harness::with_settings(SimulationSettings {
+        map: Some(MapSource::Generated(generator)),
+        ..default()
+    });
+
+    // every building stands on buildable terrain
+    let mut q_buildings = harness.app.world.query_filtered::<(), With<Building>>();
+    assert_eq!(q_buildings.iter(&harness.app.world).count(), map.buildings().count());
+    let terrain = harness.app.world.resource::<Terrain>();
+    let start = map.players[0].buildings[0].translation();
+    assert!(terrain.is_buildable(start));
+    // details can be walked past but not built on
+    let (cell, _) = terrain.details().next().unwrap();
+    assert!(terrain.is_walkable(Terrain::cell_center(cell)));
+    assert!(!terrain.is_buildable(Terrain::cell_center(cell)));
+    assert_eq!(terrain.tiles().count(), 33 * 33);
+}
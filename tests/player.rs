@@ -0,0 +1,90 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::{tree::Tree, Building},
+    economy::{Cargo, ResourceKind, Stockpile},
+    game::SimulationSettings,
+    map::MapSource,
+    map_generator::MapGenerator,
+    player::{Owner, Player, Players},
+    unit::Unit,
+};
+
+#[test]
+fn map_players_own_their_start_buildings() {
+    let mut harness = Harness::with_map("maps/default.map.ron");
+
+    let players = &harness.app.world.resource::<Players>().0;
+    assert_eq!(players.len(), 1);
+    let player = players[0];
+    assert_eq!(harness.app.world.get::<Player>(player).unwrap().color, Player::COLORS[0]);
+
+    let head_quarters = harness.building_at(Vec3::new(0.0, 0.0, 5.0));
+    assert_eq!(harness.app.world.get::<Owner>(head_quarters), Some(&Owner(player)));
+    let mut q_trees = harness.app.world.query_filtered::<Option<&Owner>, With<Tree>>();
+    assert!(q_trees.iter(&harness.app.world).all(|owner| owner.is_none()));
+}
+
+#[test]
+fn players_without_team_play_on_their_own() {
+    let harness = Harness::with_settings(SimulationSettings {
+        map: Some(MapSource::Generated(MapGenerator::default())),
+        ..default()
+    });
+
+    let players: Vec<_> = harness
+        .app
+        .world
+        .resource::<Players>()
+        .0
+        .iter()
+        .map(|player| harness.app.world.get::<Player>(*player).unwrap().clone())
+        .collect();
+    assert_eq!(players.len(), 2);
+    assert_ne!(players[0].team, players[1].team);
+    assert_ne!(players[0].color, players[1].color);
+}
+
+#[test]
+fn units_and_ways_belong_to_the_owner_of_their_start() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    let head_quarters = harness.spawn_owned_building("head_quarters", Vec3::ZERO, Some(player));
+    let tree = harness.spawn_tree(Vec3::new(4.0, 0.0, 0.0));
+
+    harness.connect(head_quarters, tree);
+    harness.seconds(6.0);
+
+    let way = harness.building(head_quarters).ways[0];
+    assert_eq!(harness.app.world.get::<Owner>(way), Some(&Owner(player)));
+    let mut q_units = harness.app.world.query_filtered::<Option<&Owner>, With<Unit>>();
+    let owners: Vec<_> = q_units.iter(&harness.app.world).collect();
+    assert!(!owners.is_empty());
+    assert!(owners.iter().all(|owner| *owner == Some(&Owner(player))));
+}
+
+#[test]
+fn players_pay_for_their_buildings_themselves() {
+    let mut harness = Harness::new();
+    let rich = harness.add_player(0);
+    let poor = harness.add_player(1);
+    let rich_head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, 5.0), Some(rich));
+    harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, -5.0), Some(poor));
+    harness.app.world.get_mut::<Stockpile>(rich_head_quarters).unwrap().add(Cargo {
+        kind: ResourceKind::Wood,
+        amount: 5,
+    });
+
+    harness.place_owned_building("wood_storage", Vec3::new(3.0, 0.0, 0.0), Some(poor));
+    let mut q_buildings = harness.app.world.query::<&Building>();
+    assert_eq!(q_buildings.iter(&harness.app.world).count(), 2);
+
+    harness.place_owned_building("wood_storage", Vec3::new(3.0, 0.0, 0.0), Some(rich));
+    let storage = harness.building_at(Vec3::new(3.0, 0.0, 0.0));
+    assert_eq!(harness.app.world.get::<Owner>(storage), Some(&Owner(rich)));
+    let stockpile = harness.app.world.get::<Stockpile>(rich_head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 0);
+}
@@ -0,0 +1,240 @@
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    ai::Difficulty,
+    building::Building,
+    combat::Health,
+    economy::{ResourceKind, Stockpile},
+    game::{MatchState, SimulationSettings},
+    map::MapSource,
+    map_generator::MapGenerator,
+    player::{Owner, Player},
+    replay::{Replay, ReplayPlayback, ReplayRecorder, SimulationId},
+    unit::Unit,
+    way::Way,
+};
+
+/// A path in the temp directory no other test uses, the file is removed when this is dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let unique = NEXT.fetch_add(1, Ordering::Relaxed);
+        let file_name = format!("flow_rts_{}_{unique}_{name}", std::process::id());
+        TempFile(std::env::temp_dir().join(file_name))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // not every test writes the file
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn ai_match() -> SimulationSettings {
+    SimulationSettings {
+        map: Some(MapSource::Generated(MapGenerator::default())),
+        ai: BTreeMap::from([(0, Difficulty::Hard), (1, Difficulty::Hard)]),
+        ..default()
+    }
+}
+
+/// Records 60 seconds of two AIs playing each other.
+fn record_ai_match() -> Replay {
+    let settings = ai_match();
+    let file = TempFile::new("recorded.replay.ron");
+    let recorder = ReplayRecorder::new(file.0.clone(), &settings);
+    let mut harness = Harness::with_setup(settings, |app| {
+        app.insert_resource(recorder);
+    });
+    harness.seconds(60.0);
+    harness.app.world.resource::<ReplayRecorder>().replay.clone()
+}
+
+/// Everything about buildings, ways and units that does not depend on their entities.
+fn snapshot(harness: &mut Harness) -> Vec<String> {
+    let world = &mut harness.app.world;
+    let players: BTreeMap<_, _> = world
+        .query::<(Entity, &Player)>()
+        .iter(world)
+        .map(|(entity, player)| (entity, player.index))
+        .collect();
+    let player = |owner: Option<&Owner>| owner.map(|owner| players[&owner.0]);
+    let mut q_ids = world.query::<&SimulationId>();
+    let mut snapshot = Vec::new();
+    let mut q_buildings = world.query_filtered::<(
+        &SimulationId,
+        &Transform,
+        Option<&Owner>,
+        Option<&Stockpile>,
+        Option<&Health>,
+    ), With<Building>>();
+    for (id, transform, owner, stockpile, health) in q_buildings.iter(world) {
+        let wood = stockpile.map(|stockpile| stockpile.amount(ResourceKind::Wood));
+        snapshot.push(format!(
+            "building {id:?} at {} of {:?} with {wood:?} wood and {health:?}",
+            transform.translation,
+            player(owner),
+        ));
+    }
+    let mut q_ways = world.query::<(&SimulationId, &Way, Option<&Owner>)>();
+    for (id, way, owner) in q_ways.iter(world) {
+        snapshot.push(format!(
+            "way {id:?} from {:?} to {:?} of {:?} with {} {:?}",
+            q_ids.get(world, way.from).ok(),
+            q_ids.get(world, way.to).ok(),
+            player(owner),
+            way.weight,
+            way.direction,
+        ));
+    }
+    let mut q_units = world.query::<(&Unit, &Transform, &Owner)>();
+    for (unit, transform, owner) in q_units.iter(world) {
+        snapshot.push(format!(
+            "{:?} at {} of {:?} with {} health",
+            unit.kind,
+            transform.translation,
+            player(Some(owner)),
+            unit.health,
+        ));
+    }
+    snapshot.sort();
+    snapshot
+}
+
+/// Updates until the playback reached `tick` and is back in the state it was in before.
+fn seek(harness: &mut Harness, tick: u64) {
+    let state = harness.state();
+    harness.app.world.resource_mut::<ReplayPlayback>().seek = Some(tick);
+    let start = Instant::now();
+    while harness.app.world.resource::<ReplayPlayback>().seek.is_some() || harness.state() != state
+    {
+        assert!(start.elapsed() < Duration::from_secs(60), "seeking did not finish");
+        harness.app.update();
+    }
+    assert_eq!(harness.current_tick(), tick);
+}
+
+#[test]
+fn replays_reproduce_the_match() {
+    let replay = record_ai_match();
+    assert!(!replay.commands.is_empty());
+    assert!(replay.commands.windows(2).all(|pair| pair[0].tick <= pair[1].tick));
+
+    let mut original = Harness::with_settings(ai_match());
+    original.seconds(60.0);
+    let mut replayed = Harness::replaying(replay);
+    replayed.seconds(60.0);
+
+    assert_eq!(replayed.current_tick(), original.current_tick());
+    assert_eq!(snapshot(&mut replayed), snapshot(&mut original));
+}
+
+/// Marks simulation entities at times that depend on the frame, like the visuals inserting their
+/// scenes.
+#[derive(Component)]
+struct Cluttered;
+
+/// Spawns and despawns entities outside of the simulation every frame and moves simulation
+/// entities to other archetypes, so entity ids and query orders differ from a plain headless run.
+fn clutter(
+    mut commands: Commands,
+    mut frame: Local<u32>,
+    mut spawned: Local<Vec<Entity>>,
+    q_simulated: Query<Entity, (Or<(With<Building>, With<Way>, With<Unit>)>, Without<Cluttered>)>,
+) {
+    *frame += 1;
+    for _ in 0..*frame % 7 * 10 {
+        spawned.push(commands.spawn_empty().id());
+    }
+    if *frame % 3 == 0 {
+        // in a scrambled order, so the simulation reuses the ids in that order
+        spawned.sort_by_key(|entity| entity.index().wrapping_mul(2_654_435_761));
+        for entity in spawned.drain(..) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for entity in q_simulated.iter().filter(|entity| entity.index() % 2 == *frame % 2) {
+        commands.entity(entity).insert(Cluttered);
+    }
+}
+
+#[test]
+fn replays_do_not_depend_on_entities_outside_the_simulation() {
+    let settings = ai_match();
+    let file = TempFile::new("cluttered.replay.ron");
+    let recorder = ReplayRecorder::new(file.0.clone(), &settings);
+    let mut original = Harness::with_setup(settings, |app| {
+        app.insert_resource(recorder).add_systems(Update, clutter);
+    });
+    original.seconds(60.0);
+    let replay = original.app.world.resource::<ReplayRecorder>().replay.clone();
+
+    let mut replayed = Harness::replaying(replay);
+    replayed.seconds(60.0);
+
+    assert_eq!(replayed.current_tick(), original.current_tick());
+    assert_eq!(snapshot(&mut replayed), snapshot(&mut original));
+}
+
+#[test]
+fn seeking_simulates_the_match_to_the_tick() {
+    let replay = record_ai_match();
+    let mut straight = Harness::replaying(replay.clone());
+    straight.seconds(20.0);
+    let at_20_seconds = (straight.current_tick(), snapshot(&mut straight));
+    straight.seconds(20.0);
+    let at_40_seconds = (straight.current_tick(), snapshot(&mut straight));
+
+    let mut seeking = Harness::replaying(replay);
+    seek(&mut seeking, at_40_seconds.0);
+    assert_eq!(snapshot(&mut seeking), at_40_seconds.1);
+    // going back restarts the match and simulates it again
+    seek(&mut seeking, at_20_seconds.0);
+    assert_eq!(snapshot(&mut seeking), at_20_seconds.1);
+}
+
+#[test]
+fn seeking_while_paused_stays_paused() {
+    let replay = record_ai_match();
+    let mut straight = Harness::replaying(replay.clone());
+    straight.seconds(30.0);
+    let at_30_seconds = (straight.current_tick(), snapshot(&mut straight));
+
+    let mut seeking = Harness::replaying(replay);
+    seeking.seconds(10.0);
+    let at_10_seconds = seeking.current_tick();
+    seeking.app.world.resource_mut::<NextState<MatchState>>().set(MatchState::Paused);
+    seeking.app.update();
+    assert_eq!(seeking.state(), MatchState::Paused);
+
+    seek(&mut seeking, at_30_seconds.0);
+    assert_eq!(snapshot(&mut seeking), at_30_seconds.1);
+    // restarting to go back does not resume the match either
+    seek(&mut seeking, at_10_seconds);
+    for _ in 0..10 {
+        seeking.app.update();
+    }
+    assert_eq!(seeking.state(), MatchState::Paused);
+    assert_eq!(seeking.current_tick(), at_10_seconds);
+}
+
+#[test]
+fn replays_survive_saving_and_loading() {
+    let replay = record_ai_match();
+    let file = TempFile::new("saved.replay.ron");
+    replay.save(&file.0).unwrap();
+    assert_eq!(Replay::load(&file.0).unwrap(), replay);
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    replay::SimulationId,
+    routing::WayGraph,
+    way::{Way, WayDirection, WayPath},
+};
+
+/// Straight way between buildings `from` and `to`, which are entities 0 to 3 on a unit square
+/// and 4 far away.
+fn way(from: u32, to: u32, direction: WayDirection) -> (Entity, Way) {
+    let position = |index: u32| {
+        [Vec3::ZERO, Vec3::X, Vec3::new(1.0, 0.0, 1.0), Vec3::Z, Vec3::splat(100.0)][index as usize]
+    };
+    let mut way = Way::new(
+        Entity::from_raw(from),
+        Entity::from_raw(to),
+        WayPath::straight(position(from), position(to)),
+    );
+    way.direction = direction;
+    (Entity::from_raw(100 + from * 10 + to), way)
+}
+
+/// Graph of `ways`, numbered in the order they are given.
+fn graph(ways: &[(Entity, Way)]) -> WayGraph {
+    let ids: Vec<_> = (0..ways.len() as u32).map(SimulationId).collect();
+    WayGraph::new(ways.iter().zip(&ids).map(|((entity, way), id)| (*entity, way, id)))
+}
+
+#[test]
+fn shortest_path_prefers_shorter_ways() {
+    let ways = [
+        way(0, 1, WayDirection::Both),
+        way(1, 2, WayDirection::Both),
+        way(0, 4, WayDirection::Both),
+        way(4, 2, WayDirection::Both),
+    ];
+    let graph = graph(&ways);
+
+    let [b1, b2] = [Entity::from_raw(1), Entity::from_raw(2)];
+    assert_eq!(graph.shortest_path(Entity::from_raw(0), b2), Some(vec![b1, b2]));
+    assert_eq!(graph.shortest_path(b2, b2), Some(vec![]));
+}
+
+#[test]
+fn shortest_path_breaks_ties_by_way_ids() {
+    let [b1, b2, b3] = [Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3)];
+    let via_1 = [way(0, 1, WayDirection::Both), way(1, 2, WayDirection::Both)];
+    let via_3 = [way(0, 3, WayDirection::Both), way(3, 2, WayDirection::Both)];
+
+    let via_3_first = graph(&[via_3.clone(), via_1.clone()].concat());
+    assert_eq!(via_3_first.shortest_path(Entity::from_raw(0), b2), Some(vec![b3, b2]));
+    let via_1_first = graph(&[via_1, via_3].concat());
+    assert_eq!(via_1_first.shortest_path(Entity::from_raw(0), b2), Some(vec![b1, b2]));
+}
+
+#[test]
+fn shortest_path_respects_directions() {
+    let ways = [way(0, 1, WayDirection::Forward), way(2, 1, WayDirection::Forward)];
+    let graph = graph(&ways);
+
+    let [b0, b1, b2] = [Entity::from_raw(0), Entity::from_raw(1), Entity::from_raw(2)];
+    assert_eq!(graph.shortest_path(b0, b1), Some(vec![b1]));
+    assert_eq!(graph.shortest_path(b1, b0), None);
+    assert_eq!(graph.shortest_path(b0, b2), None);
+}
+
+#[test]
+fn units_pass_buildings_on_their_route() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let middle = harness.spawn_tree(Vec3::new(3.0, 0.0, 0.0));
+    let target = harness.spawn_tree(Vec3::new(3.0, 0.0, 3.0));
+    harness.connect(head_quarters, middle);
+    harness.connect(middle, target);
+    let way = harness.building(head_quarters).ways[0];
+    harness.set_weight(way, 0);
+
+    harness.send_unit(head_quarters, target);
+    let units = harness.units();
+    assert_eq!((units[0].to_building, units[0].route.clone()), (middle, vec![target]));
+
+    harness.seconds(3.5);
+    let units = harness.units();
+    assert_eq!((units[0].from_building, units[0].to_building), (middle, target));
+    assert!(harness.arrivals().is_empty());
+
+    harness.seconds(3.0);
+    assert_eq!(harness.arrivals(), [target]);
+}
+
+#[test]
+fn units_reroute_when_a_way_on_their_route_is_removed() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let short_cut = harness.spawn_tree(Vec3::new(3.0, 0.0, 0.0));
+    let detour = harness.spawn_tree(Vec3::new(0.0, 0.0, 6.0));
+    let target = harness.spawn_tree(Vec3::new(3.0, 0.0, 4.0));
+    harness.connect(head_quarters, short_cut);
+    harness.connect(short_cut, target);
+    harness.connect(head_quarters, detour);
+    harness.connect(detour, target);
+    for way in harness.building(head_quarters).ways.clone() {
+        harness.set_weight(way, 0);
+    }
+
+    harness.send_unit(head_quarters, target);
+    harness.seconds(1.0);
+    let way = harness.building(target).ways[0];
+    harness.remove_way(way);
+
+    // the unit only notices at the short cut and has to go all the way back
+    harness.seconds(2.5);
+    let units = harness.units();
+    assert_eq!((units[0].from_building, units[0].to_building), (short_cut, head_quarters));
+    assert_eq!(units[0].route, [detour, target]);
+
+    // 3 seconds back to the head quarters, then about 6 + 3.6 along the detour
+    harness.seconds(11.5);
+    assert!(harness.arrivals().is_empty());
+    harness.seconds(1.0);
+    assert_eq!(harness.arrivals(), [target]);
+}
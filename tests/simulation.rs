@@ -0,0 +1,32 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::unit::Unit;
+
+fn run() -> Vec<Transform> {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let trees = [
+        harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0)),
+        harness.spawn_tree(Vec3::new(-6.0, 0.0, -8.0)),
+    ];
+    harness.connect(head_quarters, trees[0]);
+    harness.connect(head_quarters, trees[1]);
+    harness.seconds(23.0);
+
+    harness
+        .app
+        .world
+        .query_filtered::<&Transform, With<Unit>>()
+        .iter(&harness.app.world)
+        .copied()
+        .collect()
+}
+
+#[test]
+fn identical_inputs_produce_identical_state() {
+    let first = run();
+    assert!(!first.is_empty());
+    assert_eq!(first, run());
+}
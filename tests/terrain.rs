@@ -0,0 +1,97 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::{Building, SpawnBuilding},
+    command::GameCommand,
+    player::Players,
+    terrain::{Terrain, TileProperties},
+};
+
+#[test]
+fn tile_properties_follow_model_names() {
+    let open = TileProperties {
+        walkable: true,
+        buildable: true,
+    };
+    let steep = TileProperties {
+        walkable: true,
+        buildable: false,
+    };
+    let blocked = TileProperties {
+        walkable: false,
+        buildable: false,
+    };
+    assert_eq!(TileProperties::of("tile"), open);
+    assert_eq!(TileProperties::of("snow_tile_straight"), open);
+    assert_eq!(TileProperties::of("tile_hill"), steep);
+    assert_eq!(TileProperties::of("tile_riverBridge"), steep);
+    assert_eq!(TileProperties::of("tile_riverCorner"), blocked);
+    assert_eq!(TileProperties::of("snow_tile_treeQuad"), blocked);
+    assert_eq!(TileProperties::of("snow_detail_rocksLarge"), steep);
+}
+
+#[test]
+fn map_tiles_are_looked_up_by_position() {
+    let harness = Harness::with_map("maps/default.map.ron");
+    let terrain = harness.app.world.resource::<Terrain>();
+
+    assert_eq!(Terrain::cell(Vec3::new(2.3, 0.0, -4.6)), (2, -5));
+    assert_eq!(terrain.tile_at(Vec3::new(2.3, 0.0, -4.6)).unwrap().model, "tile_rock");
+    assert_eq!(terrain.tile_at(Vec3::ZERO).unwrap().model, "tile");
+    assert!(terrain.tile_at(Vec3::new(100.0, 0.0, 0.0)).is_none());
+    assert!(terrain.is_buildable(Vec3::ZERO));
+    assert!(!terrain.is_walkable(Vec3::new(2.0, 0.0, -5.0)));
+    assert!(!terrain.is_walkable(Vec3::new(100.0, 0.0, 0.0)));
+}
+
+#[test]
+fn buildings_are_only_spawned_on_buildable_tiles() {
+    let mut harness = Harness::with_map("maps/default.map.ron");
+    let mut q_buildings = harness.app.world.query::<&Building>();
+    let count = q_buildings.iter(&harness.app.world).count();
+
+    for position in [Vec3::new(2.0, 0.0, -5.0), Vec3::new(8.0, 0.0, 8.0)] {
+        harness.app.world.send_event(SpawnBuilding {
+            definition: "head_quarters".to_owned(),
+            position,
+            owner: None,
+        });
+    }
+    harness.tick();
+    assert_eq!(q_buildings.iter(&harness.app.world).count(), count);
+
+    harness.spawn_head_quarters(Vec3::new(2.0, 0.0, -8.0));
+    assert_eq!(q_buildings.iter(&harness.app.world).count(), count + 1);
+}
+
+#[test]
+fn ways_can_not_cross_blocked_tiles() {
+    let mut harness = Harness::with_map("maps/default.map.ron");
+    let player = harness.app.world.resource::<Players>().0[0];
+    let head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(2.0, 0.0, -8.0), Some(player));
+    let other = harness.spawn_head_quarters(Vec3::new(2.0, 0.0, -2.0));
+    let connect = |waypoints| GameCommand::ConnectBuildings {
+        from: head_quarters,
+        to: other,
+        waypoints,
+    };
+
+    harness.command(0, connect(Vec::new()));
+    assert!(harness.building(head_quarters).ways.is_empty());
+
+    harness.command(0, connect(vec![Vec3::new(4.0, 0.0, -5.0)]));
+    assert_eq!(harness.building(head_quarters).ways.len(), 1);
+}
+
+#[test]
+fn empty_world_has_no_restrictions() {
+    let harness = Harness::new();
+    let terrain = harness.app.world.resource::<Terrain>();
+
+    assert!(terrain.tile_at(Vec3::ZERO).is_none());
+    assert!(terrain.is_walkable(Vec3::ZERO));
+    assert!(terrain.is_buildable(Vec3::new(100.0, 0.0, 0.0)));
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use bevy::prelude::*;
+use common::Harness;
+use flow_rts::{
+    building::tree::Tree,
+    economy::{ResourceKind, Stockpile},
+    unit_kind::{UnitKind, UnitKinds},
+};
+
+#[test]
+fn head_quarters_produce_the_selected_kind() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(20.0, 0.0, 0.0));
+    harness.select_unit_kind(head_quarters, UnitKind::Scout);
+    harness.connect(head_quarters, tree);
+
+    harness.seconds(5.0);
+    harness.seconds(2.0);
+
+    let units = harness.units();
+    assert_eq!(units.len(), 1);
+    assert_eq!(units[0].kind, UnitKind::Scout);
+    let stats = harness.app.world.resource::<UnitKinds>().get(UnitKind::Scout);
+    assert_eq!(units[0].health, stats.health);
+    // it left a few ticks early, as the timer already ran while selecting and connecting
+    assert!((units[0].distance - 2.0 * stats.speed).abs() < 0.2);
+}
+
+#[test]
+fn harvested_amount_depends_on_capacity() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(3.0, 0.0, 0.0));
+    harness.select_unit_kind(head_quarters, UnitKind::Carrier);
+    harness.connect(head_quarters, tree);
+    let capacity = harness.app.world.resource::<UnitKinds>().get(UnitKind::Carrier).capacity;
+
+    // the carrier leaves after 5 seconds and walks 6 at 0.6 per second, the next one follows
+    // once the first left room at the entrance of the way and harvests after 16 seconds
+    harness.seconds(5.0 + 11.5);
+
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), capacity);
+    let tree = harness.app.world.get::<Tree>(tree).unwrap();
+    assert_eq!(tree.wood, tree.initial_wood - 2 * capacity);
+}
+
+#[test]
+fn units_without_capacity_do_not_harvest() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(2.0, 0.0, 0.0));
+    harness.select_unit_kind(head_quarters, UnitKind::Soldier);
+    harness.connect(head_quarters, tree);
+
+    harness.seconds(9.0);
+
+    assert_eq!(harness.arrivals(), [tree]);
+    let harvested = harness.app.world.get::<Tree>(tree).unwrap();
+    assert_eq!(harvested.wood, harvested.initial_wood);
+
+    // it goes back home empty
+    harness.seconds(2.0);
+    assert_eq!(harness.arrivals(), [tree, head_quarters]);
+    let stockpile = harness.app.world.get::<Stockpile>(head_quarters).unwrap();
+    assert_eq!(stockpile.amount(ResourceKind::Wood), 0);
+}
@@ -0,0 +1,317 @@
+mod common;
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use common::Harness;
+use flow_rts::{
+    command::{GameCommand, PlayerCommand},
+    input::{self, InputEvent},
+    unit::Unit,
+    way::{curve_segments, InteractWay, WayController, WayDirection, WayPath},
+};
+
+#[test]
+fn finish_spawns_way_linked_to_both_buildings() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+
+    harness.connect(head_quarters, tree);
+
+    let ways = harness.building(head_quarters).ways.clone();
+    assert_eq!(ways.len(), 1);
+    assert_eq!(harness.building(tree).ways, ways);
+    let way = harness.way(ways[0]);
+    assert_eq!((way.from, way.to, way.direction), (head_quarters, tree, WayDirection::Both));
+}
+
+#[test]
+fn duplicate_connections_are_ignored() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+
+    harness.connect(head_quarters, tree);
+    harness.connect(head_quarters, tree);
+    harness.connect(tree, head_quarters);
+    harness.connect(tree, tree);
+
+    assert_eq!(harness.building(head_quarters).ways.len(), 1);
+    assert_eq!(harness.building(tree).ways.len(), 1);
+}
+
+#[test]
+fn toggling_cycles_through_directions() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    let way = harness.building(head_quarters).ways[0];
+
+    harness.toggle_direction(way);
+    assert_eq!(harness.way(way).direction, WayDirection::Forward);
+    assert_eq!(harness.way(way).destination_from(head_quarters), Some(tree));
+    assert_eq!(harness.way(way).destination_from(tree), None);
+
+    harness.toggle_direction(way);
+    assert_eq!(harness.way(way).direction, WayDirection::Backward);
+    assert_eq!(harness.way(way).destination_from(head_quarters), None);
+    assert_eq!(harness.way(way).destination_from(tree), Some(head_quarters));
+
+    harness.toggle_direction(way);
+    assert_eq!(harness.way(way).direction, WayDirection::Both);
+}
+
+#[test]
+fn head_quarters_only_sends_units_along_allowed_directions() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(tree, head_quarters);
+    let way = harness.building(head_quarters).ways[0];
+    harness.toggle_direction(way);
+
+    harness.seconds(6.0);
+    assert!(harness.units().is_empty());
+
+    // the timer already ran out, so the first unit leaves right away
+    harness.toggle_direction(way);
+    harness.tick();
+    assert_eq!(harness.units().len(), 1);
+}
+
+#[test]
+fn harvested_wood_leaves_along_allowed_way() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    let other_head_quarters = harness.spawn_head_quarters(Vec3::new(10.0, 0.0, 5.0));
+    harness.connect(head_quarters, tree);
+    harness.connect(tree, other_head_quarters);
+    for way in harness.building(tree).ways.clone() {
+        harness.toggle_direction(way);
+    }
+
+    // the first unit leaves after 5 seconds and arrives at the tree about 7.1 seconds later
+    harness.seconds(12.5);
+
+    let loaded: Vec<_> = harness.units().into_iter().filter(|unit| unit.cargo.is_some()).collect();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded[0].to_building, other_head_quarters);
+}
+
+/// Runs [`input::issue_commands`] for a single click while placing a way from `start`.
+fn click_while_placing(
+    harness: &mut Harness,
+    start: Entity,
+    clicked: Entity,
+) -> Vec<PlayerCommand> {
+    harness.app.insert_resource(WayController {
+        material: Handle::default(),
+        start_building: Some(start),
+        waypoints: Vec::new(),
+        placing_valid: true,
+        path_clear: true,
+    });
+    harness.app.add_event::<InputEvent>();
+    harness.app.world.resource_mut::<Events<PlayerCommand>>().clear();
+    harness.app.world.send_event(InputEvent::ClickedOnBuilding {
+        building: clicked,
+    });
+    harness.app.world.run_system_once(input::issue_commands);
+    harness.app.world.resource_mut::<Events<InputEvent>>().clear();
+    harness.app.world.resource_mut::<Events<PlayerCommand>>().drain().collect()
+}
+
+#[test]
+fn clicking_other_building_requests_way() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+
+    assert!(click_while_placing(&mut harness, head_quarters, head_quarters).is_empty());
+    assert!(matches!(
+        click_while_placing(&mut harness, head_quarters, tree)[..],
+        [PlayerCommand {
+            player: 0,
+            command: GameCommand::ConnectBuildings { from, to, .. },
+        }] if from == head_quarters && to == tree
+    ));
+}
+
+#[test]
+fn ways_are_only_started_at_own_buildings() {
+    let mut harness = Harness::new();
+    let player = harness.add_player(0);
+    let enemy = harness.add_player(1);
+    let head_quarters = harness.spawn_owned_building("head_quarters", Vec3::ZERO, Some(player));
+    let enemy_head_quarters =
+        harness.spawn_owned_building("head_quarters", Vec3::new(0.0, 0.0, 8.0), Some(enemy));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+
+    // clicking only starts placing at the local player's buildings
+    let started: Vec<_> = [enemy_head_quarters, tree, head_quarters]
+        .into_iter()
+        .filter(|building| !click_to_start(&mut harness, *building).is_empty())
+        .collect();
+    assert_eq!(started, [head_quarters]);
+
+    // and the simulation rejects ways from other buildings anyway
+    for (from, to) in [(enemy_head_quarters, head_quarters), (tree, head_quarters)] {
+        harness.command(
+            0,
+            GameCommand::ConnectBuildings {
+                from,
+                to,
+                waypoints: Vec::new(),
+            },
+        );
+    }
+    assert!(harness.building(head_quarters).ways.is_empty());
+}
+
+/// Runs [`WayController::handle_input`] for a single click while not placing a way, returning
+/// the interactions it started.
+fn click_to_start(harness: &mut Harness, clicked: Entity) -> Vec<InteractWay> {
+    harness.app.insert_resource(WayController {
+        material: Handle::default(),
+        start_building: None,
+        waypoints: Vec::new(),
+        placing_valid: false,
+        path_clear: false,
+    });
+    harness.app.add_event::<InputEvent>();
+    harness.app.world.resource_mut::<Events<InteractWay>>().clear();
+    harness.app.world.send_event(InputEvent::ClickedOnBuilding {
+        building: clicked,
+    });
+    harness.app.world.run_system_once(WayController::handle_input);
+    harness.app.world.resource_mut::<Events<InputEvent>>().clear();
+    harness.app.world.resource_mut::<Events<InteractWay>>().drain().collect()
+}
+
+#[test]
+fn connecting_connected_buildings_is_rejected() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    for (from, to) in [(head_quarters, tree), (tree, head_quarters), (tree, tree)] {
+        harness.command(
+            0,
+            GameCommand::ConnectBuildings {
+                from,
+                to,
+                waypoints: Vec::new(),
+            },
+        );
+    }
+
+    assert_eq!(harness.building(head_quarters).ways.len(), 1);
+    assert_eq!(harness.building(tree).ways.len(), 1);
+}
+
+#[test]
+fn removed_way_is_unlinked_and_despawned() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+    let way = harness.building(head_quarters).ways[0];
+
+    harness.remove_way(way);
+
+    assert!(harness.app.world.get_entity(way).is_none());
+    assert!(harness.building(head_quarters).ways.is_empty());
+    assert!(harness.building(tree).ways.is_empty());
+
+    // the buildings can be connected again
+    harness.connect(tree, head_quarters);
+    assert_eq!(harness.building(head_quarters).ways.len(), 1);
+}
+
+#[test]
+fn units_on_removed_way_return() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(head_quarters, tree);
+
+    // the first unit left after 5 seconds
+    harness.seconds(7.0);
+    let way = harness.building(head_quarters).ways[0];
+    harness.remove_way(way);
+    let units = harness.units();
+    assert_eq!(units.len(), 1);
+    assert_eq!((units[0].from_building, units[0].to_building), (tree, head_quarters));
+
+    // no new units are sent without a way
+    harness.seconds(10.0);
+    assert!(harness.units().is_empty());
+    assert_eq!(harness.arrivals(), [head_quarters]);
+}
+
+#[test]
+fn path_samples_by_distance_in_both_directions() {
+    let path = WayPath::new(vec![Vec3::ZERO, Vec3::new(2.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 3.0)]);
+
+    assert_eq!(path.length(), 5.0);
+    assert_eq!(path.sample(1.0, false), (Vec3::new(1.0, 0.0, 0.0), Vec3::X));
+    assert_eq!(path.sample(4.0, false), (Vec3::new(2.0, 0.0, 2.0), Vec3::Z));
+    assert_eq!(path.sample(1.0, true), (Vec3::new(2.0, 0.0, 2.0), -Vec3::Z));
+    assert_eq!(path.sample(9.0, false).0, Vec3::new(2.0, 0.0, 3.0));
+}
+
+#[test]
+fn units_stay_on_their_way() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 5.0));
+    let tree = harness.spawn_tree(Vec3::new(5.0, 0.0, 0.0));
+    harness.connect(tree, head_quarters);
+    let way = harness.building(head_quarters).ways[0];
+
+    harness.seconds(7.0);
+
+    let (unit, transform) =
+        harness.app.world.query::<(&Unit, &Transform)>().single(&harness.app.world);
+    assert_eq!(unit.way, way);
+    assert!(unit.reversed);
+    let (position, _) = harness.way(way).path.sample(unit.distance, true);
+    assert_eq!(transform.translation, position);
+    assert!((unit.distance - 2.0).abs() < 0.1);
+}
+
+#[test]
+fn curve_passes_through_waypoints() {
+    let control_points = [Vec3::ZERO, Vec3::new(2.0, 0.0, 2.0), Vec3::new(4.0, 0.0, 0.0)];
+
+    let segments = curve_segments(&control_points);
+
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].first(), Some(&control_points[0]));
+    assert!(segments[0].last().unwrap().distance(control_points[1]) < 1e-5);
+    assert_eq!(segments[1].last(), Some(&control_points[2]));
+    let path = WayPath::from_segments(&segments);
+    assert!(path.length() > control_points[0].distance(control_points[1]) * 2.0);
+    assert_eq!(curve_segments(&control_points[..2]), [control_points[..2].to_vec()]);
+}
+
+#[test]
+fn units_follow_bent_ways() {
+    let mut harness = Harness::new();
+    let head_quarters = harness.spawn_head_quarters(Vec3::new(0.0, 0.0, 0.0));
+    let tree = harness.spawn_tree(Vec3::new(4.0, 0.0, 0.0));
+    harness.connect_via(head_quarters, tree, vec![Vec3::new(2.0, 0.0, 3.0)]);
+    let way = harness.building(head_quarters).ways[0];
+    let length = harness.way(way).path.length();
+    assert!(length > 7.0);
+
+    // leaves after 5 seconds and takes longer than the straight distance
+    harness.seconds(5.0 + 6.0);
+    assert!(harness.arrivals().is_empty());
+    let mut q_units = harness.app.world.query_filtered::<&Transform, With<Unit>>();
+    assert!(q_units.iter(&harness.app.world).any(|transform| transform.translation.z > 1.0));
+
+    harness.seconds(length as f64 - 6.0 + 0.5);
+    assert_eq!(harness.arrivals(), [tree]);
+}